@@ -1,16 +1,25 @@
 use criterion::{criterion_group, criterion_main, Criterion};
 use rust_nrm::utils::{
-    cli::{CommandExecutor, Commands},
+    cli::{CommandExecutor, Commands, TestArgs},
     registries::{Registry, Store},
 };
+use std::path::PathBuf;
 use tokio::runtime::Runtime;
 
+/// Keep benchmark writes away from the user's real config
+fn bench_config_path() -> PathBuf {
+    std::env::temp_dir()
+        .join("rust-nrm-bench")
+        .join("registries.toml")
+}
+
 fn bench_ls(c: &mut Criterion) {
     c.bench_function("cli_ls", |b| {
         b.iter(|| {
             // Create a mock store
             let mut store = Store {
                 registries: std::collections::HashMap::new(),
+                config_path: bench_config_path(),
             };
 
             // Add a sample registry to the store
@@ -19,6 +28,7 @@ fn bench_ls(c: &mut Criterion) {
                 Registry {
                     registry: "https://example.com".to_string(),
                     home: Some("https://example.com".to_string()),
+                    ..Default::default()
                 },
             );
 
@@ -26,7 +36,7 @@ fn bench_ls(c: &mut Criterion) {
             let rt = tokio::runtime::Runtime::new().unwrap();
             rt.block_on(async {
                 let mut executor = CommandExecutor::new(store);
-                executor.execute(Commands::Ls).await;
+                executor.execute(Commands::Ls { scopes: false }).await.unwrap();
             });
         })
     });
@@ -38,6 +48,7 @@ fn bench_use(c: &mut Criterion) {
             // Create a mock store
             let mut store = Store {
                 registries: std::collections::HashMap::new(),
+                config_path: bench_config_path(),
             };
 
             // Add a sample registry to the store
@@ -46,6 +57,7 @@ fn bench_use(c: &mut Criterion) {
                 Registry {
                     registry: "https://example.com".to_string(),
                     home: Some("https://example-home.com".to_string()),
+                    ..Default::default()
                 },
             );
 
@@ -56,10 +68,16 @@ fn bench_use(c: &mut Criterion) {
                 let mut executor = CommandExecutor::new(store);
                 executor
                     .execute(Commands::Use {
-                        registry: registry_name,
+                        registry: Some(registry_name),
                         local: false,
+                        pm: None,
+                        auto: false,
+                        fastest: false,
+                        among: vec![],
+                        test: TestArgs::default(),
                     })
-                    .await;
+                    .await
+                    .unwrap();
             });
         });
     });
@@ -71,6 +89,7 @@ fn bench_add(c: &mut Criterion) {
             // Create a mock store
             let store = Store {
                 registries: std::collections::HashMap::new(),
+                config_path: bench_config_path(),
             };
 
             // Simulate calling the Add command
@@ -86,8 +105,11 @@ fn bench_add(c: &mut Criterion) {
                         registry: registry_name,
                         url: registry_url,
                         home,
+                        proxy: None,
+                        force: false,
                     })
-                    .await;
+                    .await
+                    .unwrap();
             });
         });
     });
@@ -99,6 +121,7 @@ fn bench_remove(c: &mut Criterion) {
             // Create a mock store
             let mut store = Store {
                 registries: std::collections::HashMap::new(),
+                config_path: bench_config_path(),
             };
 
             // Add a sample registry to remove
@@ -107,6 +130,7 @@ fn bench_remove(c: &mut Criterion) {
                 Registry {
                     registry: "https://example.com".to_string(),
                     home: Some("https://example-home.com".to_string()),
+                    ..Default::default()
                 },
             );
 
@@ -118,8 +142,10 @@ fn bench_remove(c: &mut Criterion) {
                 executor
                     .execute(Commands::Remove {
                         registry: registry_name,
+                        force: false,
                     })
-                    .await;
+                    .await
+                    .unwrap();
             });
         });
     });
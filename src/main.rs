@@ -1,13 +1,32 @@
-use clap::Parser;
+use clap::{CommandFactory, Parser};
+use clap_complete::CompleteEnv;
 use rust_nrm::utils::{
-    cli::{Cli, CommandExecutor},
+    cli::{Cli, CommandExecutor, COMPLETE_ENV_VAR},
     registries::Store,
+    Logger, Result,
 };
 
+async fn run(cli: Cli) -> Result<()> {
+    Logger::set_stderr(cli.format.is_machine() || cli.command.writes_to_stdout());
+    Logger::set_quiet(cli.command.is_quiet());
+    let store = match &cli.config {
+        Some(path) => Store::load_from(path).await?,
+        None => Store::load().await?,
+    };
+    let mut executor = CommandExecutor::new(store).with_format(cli.format);
+    executor.execute(cli.command).await
+}
+
 #[tokio::main]
 async fn main() {
+    // Answers completion requests from the script printed by `completions`
+    CompleteEnv::with_factory(Cli::command)
+        .var(COMPLETE_ENV_VAR)
+        .complete();
+
     let cli = Cli::parse();
-    let store = Store::load().await;
-    let mut executor = CommandExecutor::new(store);
-    executor.execute(cli.command).await;
+    if let Err(e) = run(cli).await {
+        Logger::error(&e.to_string());
+        std::process::exit(e.exit_code());
+    }
 }
@@ -0,0 +1,183 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use url::Url;
+
+use super::npmrc::Npmrc;
+use super::yarnrc::{yarn_registry_key, YarnrcYml};
+
+/// Per-registry keys npm reads from `//host/path/:<key>` entries
+const AUTH_KEYS: &[&str] = &[
+    "_authToken",
+    "_auth",
+    "username",
+    "_password",
+    "email",
+    "always-auth",
+    "certfile",
+    "keyfile",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Credentials {
+    Token(String),
+    Basic {
+        username: String,
+        password: String,
+    },
+    /// Legacy base64 `_auth` value
+    Legacy(String),
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RegistryAuth {
+    pub credentials: Option<Credentials>,
+    pub always_auth: bool,
+}
+
+impl RegistryAuth {
+    /// Short description safe to print, with secrets masked
+    pub fn redacted(&self) -> String {
+        let mut parts = Vec::new();
+        match &self.credentials {
+            Some(Credentials::Token(token)) => parts.push(redact(token)),
+            Some(Credentials::Basic { username, .. }) => parts.push(format!("{}:****", username)),
+            Some(Credentials::Legacy(auth)) => parts.push(format!("_auth {}", redact(auth))),
+            None => {}
+        }
+        if self.always_auth {
+            parts.push("always-auth".to_string());
+        }
+        parts.join(", ")
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.credentials.is_none() && !self.always_auth
+    }
+}
+
+/// npm's "nerf dart" for a registry URL: `//host[:port]/path/` without
+/// scheme, credentials, query or the last path segment.
+pub fn nerf_dart(registry_url: &str) -> Option<String> {
+    let parsed = Url::parse(registry_url).ok()?;
+    let base = parsed.join(".").ok()?;
+    let host = base.host_str()?;
+    let port = base
+        .port()
+        .map(|port| format!(":{}", port))
+        .unwrap_or_default();
+    Some(format!("//{}{}{}", host, port, base.path()))
+}
+
+fn auth_key(nerf: &str, key: &str) -> String {
+    format!("{}:{}", nerf, key)
+}
+
+/// Read the credentials configured for a registry
+pub fn get_auth(npmrc: &Npmrc, registry_url: &str) -> Option<RegistryAuth> {
+    let nerf = nerf_dart(registry_url)?;
+    let get = |key: &str| npmrc.get(&auth_key(&nerf, key)).map(str::to_string);
+
+    let credentials = if let Some(token) = get("_authToken") {
+        Some(Credentials::Token(token))
+    } else if let (Some(username), Some(password)) = (get("username"), get("_password")) {
+        let password = STANDARD
+            .decode(password.as_bytes())
+            .ok()
+            .and_then(|bytes| String::from_utf8(bytes).ok())
+            .unwrap_or(password);
+        Some(Credentials::Basic { username, password })
+    } else {
+        get("_auth").map(Credentials::Legacy)
+    };
+
+    let auth = RegistryAuth {
+        credentials,
+        always_auth: get("always-auth").is_some_and(|value| value == "true"),
+    };
+    (!auth.is_empty()).then_some(auth)
+}
+
+/// Write credentials for a registry, replacing any other credential kind
+pub fn set_auth(npmrc: &mut Npmrc, registry_url: &str, auth: &RegistryAuth) -> Option<String> {
+    let nerf = nerf_dart(registry_url)?;
+
+    if let Some(credentials) = &auth.credentials {
+        for key in ["_authToken", "_auth", "username", "_password"] {
+            npmrc.remove(&auth_key(&nerf, key));
+        }
+        match credentials {
+            Credentials::Token(token) => npmrc.set(&auth_key(&nerf, "_authToken"), token),
+            Credentials::Basic { username, password } => {
+                npmrc.set(&auth_key(&nerf, "username"), username);
+                npmrc.set(&auth_key(&nerf, "_password"), &STANDARD.encode(password));
+            }
+            Credentials::Legacy(value) => npmrc.set(&auth_key(&nerf, "_auth"), value),
+        }
+    }
+    if auth.always_auth {
+        npmrc.set(&auth_key(&nerf, "always-auth"), "true");
+    }
+
+    Some(nerf)
+}
+
+/// Remove every per-registry auth key, returning the removed keys
+pub fn remove_auth(npmrc: &mut Npmrc, registry_url: &str) -> Vec<String> {
+    let Some(nerf) = nerf_dart(registry_url) else {
+        return Vec::new();
+    };
+    npmrc.remove_matching(|key| {
+        key.strip_prefix(&nerf)
+            .and_then(|rest| rest.strip_prefix(':'))
+            .is_some_and(|rest| AUTH_KEYS.contains(&rest))
+    })
+}
+
+/// Write credentials for a registry under `npmRegistries` in `.yarnrc.yml`,
+/// returning the registry key used
+pub fn set_yarn_auth(yarnrc: &mut YarnrcYml, registry_url: &str, auth: &RegistryAuth) -> String {
+    let key = yarn_registry_key(registry_url);
+
+    if let Some(credentials) = &auth.credentials {
+        for setting in ["npmAuthToken", "npmAuthIdent"] {
+            yarnrc.remove(&["npmRegistries", &key, setting]);
+        }
+        match credentials {
+            Credentials::Token(token) => {
+                yarnrc.set(&["npmRegistries", &key, "npmAuthToken"], token)
+            }
+            Credentials::Basic { username, password } => yarnrc.set(
+                &["npmRegistries", &key, "npmAuthIdent"],
+                &format!("{}:{}", username, password),
+            ),
+            // Yarn accepts the base64 form of `user:password` as-is
+            Credentials::Legacy(value) => {
+                yarnrc.set(&["npmRegistries", &key, "npmAuthIdent"], value)
+            }
+        }
+    }
+    if auth.always_auth {
+        yarnrc.set_bool(&["npmRegistries", &key, "npmAlwaysAuth"], true);
+    }
+
+    key
+}
+
+/// Remove the `npmRegistries` entry of a registry from `.yarnrc.yml`
+pub fn remove_yarn_auth(yarnrc: &mut YarnrcYml, registry_url: &str) -> bool {
+    yarnrc.remove(&["npmRegistries", &yarn_registry_key(registry_url)])
+}
+
+/// Mask a secret, keeping only its last four characters; `${VAR}` references
+/// are not secret and are shown as-is
+pub fn redact(secret: &str) -> String {
+    if secret.starts_with("${") && secret.ends_with('}') {
+        return secret.to_string();
+    }
+    let chars: Vec<char> = secret.chars().collect();
+    if chars.len() <= 8 {
+        return "****".to_string();
+    }
+    let tail: String = chars[chars.len() - 4..].iter().collect();
+    format!("****{}", tail)
+}
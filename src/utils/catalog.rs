@@ -0,0 +1,296 @@
+use clap::ValueEnum;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+use super::error::{Error, Result};
+use super::npmrc::{Npmrc, NpmrcLine};
+use super::proxy::parse_proxy_url;
+use super::registries::{
+    normalize_registry_url, same_registry_url, validate_homepage_url, Registry,
+};
+
+/// Registry managers whose config can be imported
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ImportSource {
+    /// Node nrm (`~/.nrmrc`)
+    Nrm,
+    /// yrm (`~/.yrmrc`)
+    Yrm,
+    /// A catalog file (toml, json or nrm-style ini)
+    File,
+}
+
+impl ImportSource {
+    /// Default config location of the manager
+    pub fn default_path(&self) -> Option<PathBuf> {
+        let file = match self {
+            ImportSource::Nrm => ".nrmrc",
+            ImportSource::Yrm => ".yrmrc",
+            ImportSource::File => return None,
+        };
+        dirs::home_dir().map(|home| home.join(file))
+    }
+}
+
+/// File formats of a shareable registry catalog
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum CatalogFormat {
+    /// Same layout as the rust-nrm config file
+    #[default]
+    Toml,
+    /// `{ "name": { "registry": ..., "home": ... } }`
+    Json,
+    /// nrm's `.nrmrc` layout
+    Ini,
+}
+
+impl CatalogFormat {
+    /// Guess the format from a file extension
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_lowercase();
+        match extension.as_str() {
+            "toml" => Some(CatalogFormat::Toml),
+            "json" => Some(CatalogFormat::Json),
+            "ini" | "nrmrc" | "yrmrc" => Some(CatalogFormat::Ini),
+            _ => None,
+        }
+    }
+}
+
+/// What to do when an imported name already exists with a different URL
+/// or metadata
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ConflictStrategy {
+    /// Keep the existing registry
+    #[default]
+    Skip,
+    /// Replace the existing registry
+    Overwrite,
+    /// Import under a new name (`name-1`, `name-2`, ...)
+    Rename,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MergeReport {
+    pub added: Vec<String>,
+    pub updated: Vec<String>,
+    /// `(original name, new name)`
+    pub renamed: Vec<(String, String)>,
+    /// Kept because the existing registry has a different URL
+    pub skipped: Vec<String>,
+    /// Kept because the existing registry has the same URL but a different
+    /// home or metadata
+    pub conflicting: Vec<String>,
+    pub unchanged: Vec<String>,
+}
+
+impl MergeReport {
+    /// Whether the merge modified the registries
+    pub fn has_changes(&self) -> bool {
+        !self.added.is_empty() || !self.updated.is_empty() || !self.renamed.is_empty()
+    }
+}
+
+/// Parse nrm/yrm style ini content:
+///
+/// ```ini
+/// [company]
+/// registry=https://npm.company.com/
+/// home=https://company.com
+/// ```
+pub fn parse_ini_registries(content: &str) -> Result<Vec<(String, Registry)>> {
+    let npmrc = Npmrc::parse(content);
+    let mut sections: Vec<(String, BTreeMap<String, String>)> = Vec::new();
+
+    for line in npmrc.lines() {
+        match line {
+            NpmrcLine::Section(raw) => {
+                let name = raw
+                    .trim()
+                    .trim_start_matches('[')
+                    .trim_end_matches(']')
+                    .trim()
+                    .replace("\\.", ".");
+                sections.push((name, BTreeMap::new()));
+            }
+            NpmrcLine::Entry(entry) => {
+                if let Some((_, values)) = sections.last_mut() {
+                    values.insert(entry.key.clone(), entry.value.clone());
+                }
+            }
+            _ => {}
+        }
+    }
+
+    sections
+        .into_iter()
+        .map(|(name, mut values)| {
+            let registry = values.remove("registry").ok_or_else(|| {
+                Error::Validation(format!("Registry {} has no registry URL", name))
+            })?;
+            let home = values.remove("home");
+            let proxy = values.remove("proxy");
+            let extra = values
+                .into_iter()
+                .map(|(key, value)| (key, toml::Value::String(value)))
+                .collect();
+            Ok((
+                name,
+                Registry {
+                    registry,
+                    home,
+                    proxy,
+                    extra,
+                },
+            ))
+        })
+        .collect()
+}
+
+/// Parse a catalog file, detecting the format from its content when not given
+pub fn parse_catalog(
+    content: &str,
+    format: Option<CatalogFormat>,
+) -> Result<Vec<(String, Registry)>> {
+    let format = format.unwrap_or_else(|| detect_format(content));
+    let invalid = |message: String| Error::Validation(format!("Invalid catalog: {}", message));
+
+    let registries: BTreeMap<String, Registry> = match format {
+        CatalogFormat::Ini => return parse_ini_registries(content),
+        CatalogFormat::Toml => toml::from_str(content).map_err(|e| invalid(e.to_string()))?,
+        CatalogFormat::Json => serde_json::from_str(content).map_err(|e| invalid(e.to_string()))?,
+    };
+    Ok(registries.into_iter().collect())
+}
+
+fn detect_format(content: &str) -> CatalogFormat {
+    if content.trim_start().starts_with('{') {
+        CatalogFormat::Json
+    } else if toml::from_str::<BTreeMap<String, Registry>>(content).is_ok() {
+        CatalogFormat::Toml
+    } else {
+        CatalogFormat::Ini
+    }
+}
+
+/// Serialize registries as a catalog, sorted by name
+pub fn export_registries(
+    registries: &HashMap<String, Registry>,
+    format: CatalogFormat,
+) -> Result<String> {
+    let sorted: BTreeMap<&String, &Registry> = registries.iter().collect();
+
+    match format {
+        CatalogFormat::Toml => toml::to_string_pretty(&sorted)
+            .map_err(|e| Error::Validation(format!("Failed to export registries: {}", e))),
+        CatalogFormat::Json => Ok(format!("{}\n", serde_json::to_string_pretty(&sorted)?)),
+        CatalogFormat::Ini => {
+            let mut content = String::new();
+            for (name, registry) in sorted {
+                content.push_str(&format!("[{}]\n", name.replace('.', "\\.")));
+                let mut section = Npmrc::default();
+                section.set("registry", &registry.registry);
+                if let Some(home) = &registry.home {
+                    section.set("home", home);
+                }
+                if let Some(proxy) = &registry.proxy {
+                    section.set("proxy", proxy);
+                }
+                for (key, value) in &registry.extra {
+                    match value {
+                        toml::Value::String(value) => section.set(key, value),
+                        value => section.set(key, &value.to_string()),
+                    }
+                }
+                content.push_str(&section.to_string());
+                content.push('\n');
+            }
+            Ok(content.trim_end().to_string() + "\n")
+        }
+    }
+}
+
+/// Validate imported registries the way `add` does, normalizing their URLs
+pub fn normalize_registries(
+    registries: Vec<(String, Registry)>,
+) -> Result<Vec<(String, Registry)>> {
+    registries
+        .into_iter()
+        .map(|(name, mut registry)| {
+            registry.registry = normalize_registry_url(&registry.registry)?;
+            if let Some(home) = &registry.home {
+                validate_homepage_url(home)?;
+            }
+            if let Some(proxy) = &registry.proxy {
+                parse_proxy_url(proxy)?;
+            }
+            Ok((name, registry))
+        })
+        .collect()
+}
+
+/// Merge imported registries into `target` according to `strategy`
+pub fn merge_registries(
+    target: &mut HashMap<String, Registry>,
+    incoming: Vec<(String, Registry)>,
+    strategy: ConflictStrategy,
+) -> MergeReport {
+    let mut report = MergeReport::default();
+
+    for (name, registry) in incoming {
+        match target.get(&name) {
+            None => {
+                target.insert(name.clone(), registry);
+                report.added.push(name);
+            }
+            Some(existing) if same_registry_url(&existing.registry, &registry.registry) => {
+                // Missing home/proxy/metadata in the import is not a difference
+                let same_metadata = registry
+                    .home
+                    .as_ref()
+                    .is_none_or(|home| existing.home.as_ref() == Some(home))
+                    && registry
+                        .proxy
+                        .as_ref()
+                        .is_none_or(|proxy| existing.proxy.as_ref() == Some(proxy))
+                    && registry
+                        .extra
+                        .iter()
+                        .all(|(key, value)| existing.extra.get(key) == Some(value));
+                if same_metadata {
+                    report.unchanged.push(name);
+                } else if strategy == ConflictStrategy::Skip {
+                    report.conflicting.push(name);
+                } else {
+                    target.insert(name.clone(), registry);
+                    report.updated.push(name);
+                }
+            }
+            Some(_) => match strategy {
+                ConflictStrategy::Skip => report.skipped.push(name),
+                ConflictStrategy::Overwrite => {
+                    target.insert(name.clone(), registry);
+                    report.updated.push(name);
+                }
+                ConflictStrategy::Rename => {
+                    // Importing the same file twice should not pile up copies
+                    if target
+                        .values()
+                        .any(|r| same_registry_url(&r.registry, &registry.registry))
+                    {
+                        report.unchanged.push(name);
+                        continue;
+                    }
+                    let new_name = (1..)
+                        .map(|index| format!("{}-{}", name, index))
+                        .find(|candidate| !target.contains_key(candidate))
+                        .unwrap();
+                    target.insert(new_name.clone(), registry);
+                    report.renamed.push((name, new_name));
+                }
+            },
+        }
+    }
+
+    report
+}
@@ -1,12 +1,44 @@
 use colored::Colorize;
-use regex::Regex;
+use std::collections::HashMap;
 use std::env;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 use tokio::fs::{read_to_string, write};
+use tokio::io::AsyncReadExt;
 
-use crate::utils::registries::Registry;
-use clap::{Parser, Subcommand};
+use crate::utils::auth::{Credentials, RegistryAuth};
+use crate::utils::catalog::{
+    export_registries, merge_registries, normalize_registries, parse_catalog, parse_ini_registries,
+    CatalogFormat, ConflictStrategy, ImportSource, MergeReport,
+};
+use crate::utils::history::{
+    self, append_history, history_path, load_history, parse_age, summarize, HistoryEntry,
+};
+use crate::utils::marker::{find_marker, pin_marker, PLAIN_MARKER, TOML_MARKER};
+use crate::utils::npmrc::normalize_scope;
+use crate::utils::output::{
+    print_json, print_record, print_records, print_scopes, print_trends, CurrentReport,
+    OutputFormat, RegistryRecord,
+};
+use crate::utils::package_manager::PackageManager;
+use crate::utils::proxy::{load_proxy_config, parse_proxy_url};
+use crate::utils::registries::{
+    get_config_path, normalize_registry_url, validate_homepage_url, Registry,
+};
+use crate::utils::resolve::{current_dir, find_project_root, project_dir};
+use crate::utils::shell::{Shell, HOOK_ENV_VAR};
+use crate::utils::speed_test::{
+    PackageSpec, SpeedTestResult, SpeedTester, SpeedTesterConfig, DEFAULT_TEST_PACKAGE,
+};
+use crate::utils::targets::{
+    load_target_at, local_target_path, save_target, target_path, RegistryTarget,
+};
+use clap::{ArgGroup, Args, CommandFactory, Parser, Subcommand, ValueEnum};
+use clap_complete::engine::{ArgValueCandidates, CompletionCandidate};
+use clap_complete::env::Shells;
+use clap_complete::Shell as CompletionShell;
 
+use super::error::{Error, Result};
 use super::{registries::Store, Logger};
 
 #[derive(Parser, Debug)]
@@ -17,6 +49,15 @@ use super::{registries::Store, Logger};
     long_about = "RNRM helps you easily switch between different npm registries. It supports both global and local registry configuration."
 )]
 pub struct Cli {
+    /// Path of the registries config file (overrides RUST_NRM_CONFIG and XDG_CONFIG_HOME)
+    #[arg(long, global = true, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+
+    /// Output format: json/tsv for ls, test, current and history (logs go to
+    /// stderr), or the toml/json/ini catalog format for export and import
+    #[arg(long, global = true, value_enum, default_value_t = OutputFormat::Text)]
+    pub format: OutputFormat,
+
     #[command(subcommand)]
     pub command: Commands,
 }
@@ -28,34 +69,161 @@ pub enum Commands {
     #[command(
         long_about = "Display a list of all configured registries with their URLs. Currently active registries (global/local) will be highlighted."
     )]
-    Ls,
+    Ls {
+        /// Show `@scope:registry` mappings instead of the registry list
+        #[arg(long, default_value_t = false)]
+        scopes: bool,
+    },
 
     /// Switch to a different registry
     #[command(about = "Switch to a different registry")]
     #[command(
-        long_about = "Change the active npm registry. Use --local flag to change only the current directory's registry."
+        long_about = "Change the active npm registry. Use --local flag to change only the current project's registry (in the config at the root next to package.json), or --auto to apply the registry and scopes pinned by the nearest rust-nrm.toml or .nrmrc to the local config next to it. --fastest runs the speed test (optionally only on the --among registries, with the same options as `test`) and switches to the registry with the lowest median latency. With --pm the registry is written to that package manager's config instead (.yarnrc, .yarnrc.yml, pnpm's rc or bunfig.toml), and --pm all updates every package manager found in the project or on PATH. Without --pm a global change goes to the npm user config, and a local one to the package manager detected from the packageManager field of package.json, or its config and lockfiles."
     )]
+    // The flattened speed test options only apply to --fastest
+    #[command(mut_group("TestArgs", |group| {
+        group.requires("fastest").conflicts_with_all(["registry", "auto"])
+    }))]
     Use {
         /// Name of the registry to use (e.g., npm, yarn, taobao)
-        #[arg(required = true, value_name = "REGISTRY")]
-        registry: String,
+        #[arg(
+            value_name = "REGISTRY",
+            required_unless_present_any = ["auto", "fastest"],
+            conflicts_with_all = ["auto", "fastest"],
+            add = ArgValueCandidates::new(registry_candidates)
+        )]
+        registry: Option<String>,
 
-        /// Apply changes only to the current directory
+        /// Apply changes only to the current project (config at the project root)
         #[arg(short, long, default_value_t = false)]
         local: bool,
+
+        /// Package manager config to write (npm globally, detected from the project with --local)
+        #[arg(long, value_enum)]
+        pm: Option<PackageManager>,
+
+        /// Apply the registry pinned by the nearest rust-nrm.toml or .nrmrc
+        #[arg(long, default_value_t = false, conflicts_with_all = ["local", "fastest"])]
+        auto: bool,
+
+        /// Test the registries and switch to the one with the lowest median latency
+        #[arg(long, default_value_t = false)]
+        fastest: bool,
+
+        /// Only consider these registries for --fastest
+        #[arg(
+            long,
+            value_name = "NAME,NAME",
+            value_delimiter = ',',
+            requires = "fastest",
+            conflicts_with = "registry",
+            add = ArgValueCandidates::new(registry_candidates)
+        )]
+        among: Vec<String>,
+
+        /// Speed test options for --fastest
+        #[command(flatten, next_help_heading = "Speed test (--fastest)")]
+        test: TestArgs,
+    },
+
+    /// Pin a registry for the current project
+    #[command(about = "Pin a registry for the current project")]
+    #[command(
+        long_about = "Record the registry in a rust-nrm.toml marker at the project root (next to the nearest package.json), or update an existing .nrmrc. `rust-nrm use --auto` applies the nearest marker to the project's local config."
+    )]
+    Pin {
+        /// Name of the registry to pin
+        #[arg(
+            required = true,
+            value_name = "REGISTRY",
+            add = ArgValueCandidates::new(registry_candidates)
+        )]
+        registry: String,
+    },
+
+    /// Print shell commands that select a registry
+    #[command(about = "Print shell commands that select a registry")]
+    #[command(
+        long_about = "Print exports of npm_config_registry, YARN_NPM_REGISTRY_SERVER and BUN_CONFIG_REGISTRY for `eval`, so the registry only changes for the current shell and no config file is edited. With --auto the registry pinned by the nearest rust-nrm.toml or .nrmrc is used and the exported names are recorded in RUST_NRM_ENV; when there is none, only the variables listed there are unset."
+    )]
+    Env {
+        /// Name of the registry to use (e.g., npm, yarn, taobao)
+        #[arg(
+            value_name = "REGISTRY",
+            required_unless_present = "auto",
+            conflicts_with = "auto",
+            add = ArgValueCandidates::new(registry_candidates)
+        )]
+        registry: Option<String>,
+
+        /// Use the registry pinned by the nearest rust-nrm.toml or .nrmrc
+        #[arg(long, default_value_t = false)]
+        auto: bool,
+
+        /// Shell syntax to print (detected from $SHELL by default)
+        #[arg(long, value_enum)]
+        shell: Option<Shell>,
+    },
+
+    /// Print a hook that applies project markers on cd
+    #[command(about = "Print a hook that applies project markers on cd")]
+    #[command(
+        long_about = "Print a shell hook that runs `rust-nrm env --auto` whenever the working directory changes. Add `eval \"$(rust-nrm shell-init bash)\"` to ~/.bashrc (or the zsh, fish or PowerShell equivalent)."
+    )]
+    ShellInit {
+        /// Shell to print the hook for
+        #[arg(required = true, value_enum)]
+        shell: Shell,
+    },
+
+    /// Generate shell completions
+    #[command(about = "Generate shell completions")]
+    #[command(
+        long_about = "Print a script that registers completions with the shell, e.g. `source <(rust-nrm completions bash)`. Registry arguments (use, remove, set-auth, ...) complete the names currently stored in the config file."
+    )]
+    Completions {
+        /// Shell to generate the script for
+        #[arg(required = true, value_enum)]
+        shell: CompletionShell,
+    },
+
+    /// Show the registry currently in use
+    #[command(about = "Show the registry currently in use")]
+    #[command(
+        long_about = "Resolve the registry npm will use in this directory, in npm's order: the npm_config_registry environment variable, the project .npmrc (next to the nearest package.json), the user ~/.npmrc, $PREFIX/etc/npmrc and finally the builtin default."
+    )]
+    Current {
+        /// Show which config layer provided the registry
+        #[arg(short, long, default_value_t = false)]
+        verbose: bool,
     },
 
     /// Test registry response times
     #[command(about = "Test registry response times")]
     #[command(
-        long_about = "Measure and compare response times for all configured registries to help you choose the fastest one."
+        long_about = "Measure and compare response times for all configured registries to help you choose the fastest one. Each registry gets --warmup unmeasured requests to open the connection, then --samples measured ones; registries answering every sample are ranked first, then by the share of samples answered and their median response time. With --package (lodash by default) every sample fetches the package's packument and downloads its tarball, reporting time-to-first-byte, total time and throughput. With --detailed every sample opens a new connection and DNS lookup, TCP connect, TLS handshake, time-to-first-byte and body transfer are timed separately. Redirects are followed, up to 10, with the phases of every request added up."
+    )]
+    Test(TestArgs),
+
+    /// Show how registry speeds changed over recorded tests
+    #[command(about = "Show how registry speeds changed over recorded tests")]
+    #[command(
+        long_about = "Summarize the runs saved by `test --record`: per registry the number of runs, average median response time, failure rate, how the newer half of the runs compares to the older half, and a sparkline of every run (× marks a failed one). The history lives in rust-nrm/history.jsonl under the user data directory, or in $RUST_NRM_HISTORY."
     )]
-    Test,
+    History {
+        /// Only show this registry
+        #[arg(long, add = ArgValueCandidates::new(registry_candidates))]
+        registry: Option<String>,
+
+        /// Only include runs newer than this, e.g. 12h, 7d or 2w
+        #[arg(long, value_name = "AGE", value_parser = parse_age)]
+        since: Option<Duration>,
+    },
 
     /// Add a new registry
     #[command(about = "Add a new registry")]
     #[command(
-        long_about = "Add a custom registry with its URL and optional homepage. The registry will be available for use immediately."
+        long_about = "Add a custom registry with its URL and optional homepage. The URL must use http or https and is normalized to end with a slash. Existing names are only replaced with --force."
     )]
     Add {
         /// Name for the new registry
@@ -69,119 +237,1272 @@ pub enum Commands {
         /// Homepage URL for the registry
         #[arg(value_name = "HOMEPAGE")]
         home: Option<String>,
+
+        /// Proxy for requests to this registry (e.g., http://proxy.corp.com:8080)
+        #[arg(long, value_name = "URL")]
+        proxy: Option<String>,
+
+        /// Overwrite an existing registry with the same name
+        #[arg(short, long, default_value_t = false)]
+        force: bool,
+    },
+
+    /// Route a package scope to a registry
+    #[command(about = "Route a package scope to a registry")]
+    #[command(
+        long_about = "Write an `@scope:registry` entry so packages under the scope are installed from the given registry while everything else keeps using the default one."
+    )]
+    SetScope {
+        /// Package scope (e.g., @company)
+        #[arg(required = true, value_name = "SCOPE")]
+        scope: String,
+
+        /// Name of the registry the scope should use
+        #[arg(
+            required = true,
+            value_name = "REGISTRY",
+            add = ArgValueCandidates::new(registry_candidates)
+        )]
+        registry: String,
+
+        /// Apply changes only to the current project (config at the project root)
+        #[arg(short, long, default_value_t = false)]
+        local: bool,
+
+        /// Package manager config to write (npm globally, detected from the project with --local)
+        #[arg(long, value_enum)]
+        pm: Option<PackageManager>,
+    },
+
+    /// Remove a scoped registry
+    #[command(about = "Remove a scoped registry")]
+    #[command(
+        long_about = "Delete the `@scope:registry` entry so the scope falls back to the default registry."
+    )]
+    DelScope {
+        /// Package scope (e.g., @company)
+        #[arg(required = true, value_name = "SCOPE")]
+        scope: String,
+
+        /// Apply changes only to the current project (config at the project root)
+        #[arg(short, long, default_value_t = false)]
+        local: bool,
+
+        /// Package manager config to write (npm globally, detected from the project with --local)
+        #[arg(long, value_enum)]
+        pm: Option<PackageManager>,
+    },
+
+    /// Store credentials for a registry
+    #[command(about = "Store credentials for a registry")]
+    #[command(
+        long_about = "Write the `//host/path/:_authToken` (or username/password) entries npm uses to authenticate against the registry."
+    )]
+    #[command(group(
+        ArgGroup::new("credentials")
+            .required(true)
+            .multiple(true)
+            .args(["token", "username", "always_auth"])
+    ))]
+    SetAuth {
+        /// Name of the registry to authenticate against
+        #[arg(
+            required = true,
+            value_name = "REGISTRY",
+            add = ArgValueCandidates::new(registry_candidates)
+        )]
+        registry: String,
+
+        /// Auth token (e.g., a GitHub personal access token)
+        #[arg(long, conflicts_with_all = ["username", "password"])]
+        token: Option<String>,
+
+        /// Username for basic authentication
+        #[arg(long, requires = "password")]
+        username: Option<String>,
+
+        /// Password for basic authentication
+        #[arg(long, requires = "username")]
+        password: Option<String>,
+
+        /// Always send credentials, even for GET requests
+        #[arg(long, default_value_t = false)]
+        always_auth: bool,
+
+        /// Apply changes only to the current project (config at the project root)
+        #[arg(short, long, default_value_t = false)]
+        local: bool,
+
+        /// Package manager config to write (npm globally, detected from the project with --local)
+        #[arg(long, value_enum)]
+        pm: Option<PackageManager>,
+    },
+
+    /// Remove stored credentials for a registry
+    #[command(about = "Remove stored credentials for a registry")]
+    #[command(
+        long_about = "Delete every `//host/path/:` auth entry (token, username, password, always-auth) for the registry."
+    )]
+    DelAuth {
+        /// Name of the registry
+        #[arg(
+            required = true,
+            value_name = "REGISTRY",
+            add = ArgValueCandidates::new(registry_candidates)
+        )]
+        registry: String,
+
+        /// Apply changes only to the current project (config at the project root)
+        #[arg(short, long, default_value_t = false)]
+        local: bool,
+
+        /// Package manager config to write (npm globally, detected from the project with --local)
+        #[arg(long, value_enum)]
+        pm: Option<PackageManager>,
+    },
+
+    /// Export the registry catalog
+    #[command(about = "Export the registry catalog")]
+    #[command(
+        long_about = "Write every registry (with its homepage and metadata) to a shareable file that can be loaded with `rust-nrm import`. The format is taken from --format (toml, json or ini), then the --output extension, and defaults to TOML."
+    )]
+    Export {
+        /// File to write instead of stdout
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+
+    /// Import registries from a catalog or another registry manager
+    #[command(about = "Import registries from a catalog file, nrm or yrm")]
+    #[command(
+        long_about = "Merge registries from a catalog file (toml, json or ini, detected unless --format is given; `-` reads stdin), or from the Node nrm (~/.nrmrc) or yrm (~/.yrmrc) config, into the rust-nrm config. The changes are listed before they are applied."
+    )]
+    Import {
+        /// Catalog file to read, `-` for stdin (defaults to the manager's usual location)
+        #[arg(value_name = "FILE")]
+        file: Option<PathBuf>,
+
+        /// Manager the config comes from
+        #[arg(long, value_enum)]
+        from: Option<ImportSource>,
+
+        /// How to handle names that already exist with a different URL or metadata
+        #[arg(long, value_enum, default_value_t = ConflictStrategy::Skip)]
+        strategy: ConflictStrategy,
+
+        /// Only show what would change
+        #[arg(long, default_value_t = false)]
+        dry_run: bool,
     },
 
     /// Remove a registry
     #[command(about = "Remove a registry", alias = "rm")]
     #[command(
-        long_about = "Remove a registry from the configuration. Built-in registries cannot be removed."
+        long_about = "Remove a registry from the configuration. Built-in registries are only removed with --force."
     )]
     Remove {
         /// Name of the registry to remove
-        #[arg(required = true, value_name = "REGISTRY")]
+        #[arg(
+            required = true,
+            value_name = "REGISTRY",
+            add = ArgValueCandidates::new(registry_candidates)
+        )]
         registry: String,
+
+        /// Allow removing a built-in registry
+        #[arg(short, long, default_value_t = false)]
+        force: bool,
     },
 }
 
+/// Options of `test`
+#[derive(Args, Debug, Clone, PartialEq)]
+pub struct TestArgs {
+    /// Measured requests per registry
+    #[arg(long, default_value_t = 3, value_parser = clap::value_parser!(u32).range(1..))]
+    pub samples: u32,
+
+    /// Unmeasured requests sent first, so connection setup is not counted
+    #[arg(long, default_value_t = 1)]
+    pub warmup: u32,
+
+    /// Fetch a package's packument and tarball instead of the registry root
+    #[arg(
+        long,
+        value_name = "NAME[@VERSION]",
+        num_args = 0..=1,
+        default_missing_value = DEFAULT_TEST_PACKAGE
+    )]
+    pub package: Option<String>,
+
+    /// Time DNS, TCP connect, TLS, time-to-first-byte and body separately
+    #[arg(long, default_value_t = false, conflicts_with = "package")]
+    pub detailed: bool,
+
+    /// Milliseconds before a request fails as timed out
+    #[arg(long, value_name = "MS", default_value_t = 5000, value_parser = clap::value_parser!(u64).range(1..))]
+    pub timeout: u64,
+
+    /// Registries tested at the same time
+    #[arg(long, value_name = "N", default_value_t = 8, value_parser = clap::value_parser!(u32).range(1..))]
+    pub concurrency: u32,
+
+    /// Extra attempts for a failed request, waiting longer before each
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    pub retries: u32,
+
+    /// Append the results to the speed test history (see `history`)
+    #[arg(long, default_value_t = false)]
+    pub record: bool,
+
+    /// Send every request through this proxy instead of the npmrc and
+    /// HTTP(S)_PROXY settings (NO_PROXY and noproxy still apply)
+    #[arg(long, value_name = "URL")]
+    pub proxy: Option<String>,
+}
+
+impl Default for TestArgs {
+    fn default() -> Self {
+        Self {
+            samples: 3,
+            warmup: 1,
+            package: None,
+            detailed: false,
+            timeout: 5000,
+            concurrency: 8,
+            retries: 0,
+            record: false,
+            proxy: None,
+        }
+    }
+}
+
+impl Commands {
+    /// Commands whose stdout is data for other programs, so logs must go to stderr
+    pub fn writes_to_stdout(&self) -> bool {
+        matches!(
+            self,
+            Commands::Export { output: None, .. }
+                | Commands::Env { .. }
+                | Commands::ShellInit { .. }
+                | Commands::Completions { .. }
+        )
+    }
+
+    /// Commands run from shell hooks, where INFO chatter would show on every prompt
+    pub fn is_quiet(&self) -> bool {
+        matches!(
+            self,
+            Commands::Env { .. } | Commands::ShellInit { .. } | Commands::Completions { .. }
+        )
+    }
+}
+
+/// Environment variable the shell sets when it asks `rust-nrm` for completions
+pub const COMPLETE_ENV_VAR: &str = "COMPLETE";
+
+/// Completion candidates for registry name arguments. Completion runs
+/// before the CLI is parsed, so the default config location is read.
+fn registry_candidates() -> Vec<CompletionCandidate> {
+    let registries: HashMap<String, Registry> = get_config_path(None)
+        .ok()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .and_then(|content| toml::from_str(&content).ok())
+        .unwrap_or_else(Store::builtin_registries);
+
+    let mut names: Vec<_> = registries.into_iter().collect();
+    names.sort_by_key(|(name, _)| name.to_lowercase());
+    names
+        .into_iter()
+        .map(|(name, registry)| CompletionCandidate::new(name).help(Some(registry.registry.into())))
+        .collect()
+}
+
+/// Script registering `rust-nrm` with the shell's completion system. The
+/// shell calls back into `rust-nrm` with `COMPLETE` set on every completion.
+pub fn completion_script(shell: CompletionShell) -> Result<String> {
+    let name = Cli::command().get_name().to_string();
+    let shells = Shells::builtins();
+    let completer = shells
+        .completer(&shell.to_string())
+        .ok_or_else(|| Error::Validation(format!("Completions are not supported for {}", shell)))?;
+
+    let mut script = Vec::new();
+    completer
+        .write_registration(COMPLETE_ENV_VAR, &name, &name, &name, &mut script)
+        .map_err(|source| Error::Io {
+            path: PathBuf::from("-"),
+            source,
+        })?;
+    Ok(String::from_utf8_lossy(&script).into_owned())
+}
+
 pub struct CommandExecutor {
     store: Store,
+    format: OutputFormat,
 }
 
 impl CommandExecutor {
     pub fn new(store: Store) -> Self {
-        Self { store }
+        Self {
+            store,
+            format: OutputFormat::Text,
+        }
+    }
+
+    pub fn with_format(mut self, format: OutputFormat) -> Self {
+        self.format = format;
+        self
     }
 
-    pub async fn execute(&mut self, command: Commands) {
+    pub async fn execute(&mut self, command: Commands) -> Result<()> {
+        if self.format.is_catalog_only()
+            && !matches!(command, Commands::Export { .. } | Commands::Import { .. })
+        {
+            return Err(Error::Validation(format!(
+                "--format {} is only supported by export and import",
+                format_name(self.format)
+            )));
+        }
         match command {
-            Commands::Ls => self.handle_list().await,
-            Commands::Use { registry, local } => self.handle_use(registry, local).await,
-            Commands::Test => self.handle_test().await,
-            Commands::Add { registry, url, home } => self.handle_add(registry, url, home).await,
-            Commands::Remove { registry } => self.handle_remove(registry).await,
+            Commands::Ls { scopes } => self.handle_list(scopes).await,
+            Commands::Use {
+                registry,
+                local,
+                pm,
+                auto,
+                fastest,
+                among,
+                test,
+            } => {
+                if auto {
+                    self.handle_use_auto(pm).await
+                } else if fastest {
+                    self.handle_use_fastest(among, test, local, pm).await
+                } else {
+                    let registry = registry.ok_or_else(|| {
+                        Error::Validation(
+                            "A registry name, --auto or --fastest is required".to_string(),
+                        )
+                    })?;
+                    self.handle_use(registry, local, pm).await
+                }
+            }
+            Commands::Pin { registry } => self.handle_pin(registry).await,
+            Commands::Env {
+                registry,
+                auto,
+                shell,
+            } => {
+                self.handle_env(registry, auto, shell.unwrap_or_else(Shell::detect))
+                    .await
+            }
+            Commands::ShellInit { shell } => {
+                print!("{}", shell.init_script());
+                Ok(())
+            }
+            Commands::Completions { shell } => self.handle_completions(shell),
+            Commands::Current { verbose } => self.handle_current(verbose).await,
+            Commands::Test(args) => self.handle_test(args).await,
+            Commands::History { registry, since } => self.handle_history(registry, since).await,
+            Commands::Add {
+                registry,
+                url,
+                home,
+                proxy,
+                force,
+            } => self.handle_add(registry, url, home, proxy, force).await,
+            Commands::SetScope {
+                scope,
+                registry,
+                local,
+                pm,
+            } => self.handle_set_scope(scope, registry, local, pm).await,
+            Commands::DelScope { scope, local, pm } => {
+                self.handle_del_scope(scope, local, pm).await
+            }
+            Commands::SetAuth {
+                registry,
+                token,
+                username,
+                password,
+                always_auth,
+                local,
+                pm,
+            } => {
+                let credentials = match (token, username, password) {
+                    (Some(token), _, _) => Some(Credentials::Token(token)),
+                    (None, Some(username), Some(password)) => {
+                        Some(Credentials::Basic { username, password })
+                    }
+                    _ => None,
+                };
+                let auth = RegistryAuth {
+                    credentials,
+                    always_auth,
+                };
+                self.handle_set_auth(registry, auth, local, pm).await
+            }
+            Commands::DelAuth {
+                registry,
+                local,
+                pm,
+            } => self.handle_del_auth(registry, local, pm).await,
+            Commands::Export { output } => self.handle_export(output).await,
+            Commands::Import {
+                file,
+                from,
+                strategy,
+                dry_run,
+            } => self.handle_import(file, from, strategy, dry_run).await,
+            Commands::Remove { registry, force } => self.handle_remove(registry, force).await,
         }
     }
 
-    async fn handle_list(&mut self) {
-        self.store.list_registries().await;
+    async fn handle_list(&mut self, scopes: bool) -> Result<()> {
+        if self.format.is_machine() {
+            return if scopes {
+                print_scopes(self.format, &self.store.scope_records().await?)
+            } else {
+                print_records(self.format, &self.store.registry_records().await?)
+            };
+        }
+
+        if scopes {
+            self.store.list_scopes().await
+        } else {
+            self.store.list_registries().await
+        }
     }
 
-    async fn handle_use(&mut self, registry: String, local: bool) {
-        if let Some(registry_data) = self.store.registries.get(&registry) {
-            let registry_text = format!("registry={}", registry_data.registry);
-            let npmrc_path = if local {
-                ".npmrc".to_string()
-            } else {
-                let home_dir = env::var("HOME").expect("Failed to get HOME directory");
-                format!("{}/.npmrc", home_dir)
+    async fn handle_use(
+        &mut self,
+        registry: String,
+        local: bool,
+        pm: Option<PackageManager>,
+    ) -> Result<()> {
+        let url = self.store.get_registry(&registry)?.registry.clone();
+
+        let mut targets = load_targets(pm, local).await?;
+        for target in targets.iter_mut() {
+            log_config_path(target.path());
+            target.set_registry(&url);
+        }
+        save_targets(&targets).await?;
+
+        self.store.save().await?;
+        Logger::success(&format!(
+            "{} {} registry updated!",
+            if local { "Local" } else { "Global" },
+            target_names(&targets)
+        ));
+        Ok(())
+    }
+
+    async fn handle_use_auto(&mut self, pm: Option<PackageManager>) -> Result<()> {
+        let found = find_marker(&current_dir()?).await?.ok_or_else(|| {
+            Error::NotFound(format!(
+                "Project marker ({} or {})",
+                TOML_MARKER, PLAIN_MARKER
+            ))
+        })?;
+        Logger::info(&format!("Using marker {}", found.path.display()));
+
+        let url = match &found.marker.registry {
+            Some(registry) => Some(self.resolve_registry_ref(registry)?),
+            None => None,
+        };
+        let scopes = found
+            .marker
+            .scopes
+            .iter()
+            .map(|(scope, registry)| {
+                Ok((parse_scope(scope)?, self.resolve_registry_ref(registry)?))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut targets = load_targets_in(pm, true, found.dir()).await?;
+        for target in targets.iter_mut() {
+            log_config_path(target.path());
+            if let Some(url) = &url {
+                target.set_registry(url);
+            }
+            for (scope, url) in &scopes {
+                target.set_scope(scope, url);
+            }
+        }
+        save_targets(&targets).await?;
+
+        Logger::success(&format!(
+            "Local {} registry updated from {}!",
+            target_names(&targets),
+            found.path.display()
+        ));
+        Ok(())
+    }
+
+    async fn handle_use_fastest(
+        &mut self,
+        among: Vec<String>,
+        args: TestArgs,
+        local: bool,
+        pm: Option<PackageManager>,
+    ) -> Result<()> {
+        let tester = speed_tester(&args, &self.store.registries).await?;
+        let results = if among.is_empty() {
+            self.store.test_registry_speed(&tester).await?
+        } else {
+            self.store.test_registries_speed(&tester, &among).await?
+        };
+        if args.record {
+            record_results(&results).await?;
+        }
+
+        // Results are ranked, so the first successful one is the fastest
+        let mut successful = results.iter().filter(|result| result.is_success);
+        let fastest = successful.next().ok_or_else(|| {
+            Error::Validation("No registry answered successfully, nothing was changed".to_string())
+        })?;
+
+        let mut reason = format!(
+            "{} has the lowest median latency ({:.0}ms over {} sample{}, {:.0}% successful)",
+            fastest.name.bold(),
+            fastest.response_time * 1000.0,
+            args.samples,
+            if args.samples == 1 { "" } else { "s" },
+            fastest.success_ratio * 100.0
+        );
+        if let Some(runner_up) = successful.next() {
+            reason.push_str(&format!(
+                ", {:.0}ms ahead of {}",
+                (runner_up.response_time - fastest.response_time) * 1000.0,
+                runner_up.name
+            ));
+        }
+        Logger::info(&reason);
+
+        self.handle_use(fastest.name.clone(), local, pm).await
+    }
+
+    /// Registry URL for a marker value: a stored name, or a URL as-is
+    fn resolve_registry_ref(&self, registry: &str) -> Result<String> {
+        match self.store.get_registry(registry) {
+            Ok(data) => Ok(data.registry.clone()),
+            Err(_) if registry.contains("://") => normalize_registry_url(registry),
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn handle_pin(&mut self, registry: String) -> Result<()> {
+        self.store.get_registry(&registry)?;
+
+        let root = find_project_root(&current_dir()?);
+        let path = pin_marker(&root, &registry).await?;
+        Logger::success(&format!(
+            "Pinned {} in {}, apply it with `rust-nrm use --auto`",
+            registry.green().bold(),
+            path.display()
+        ));
+        Ok(())
+    }
+
+    async fn handle_env(
+        &mut self,
+        registry: Option<String>,
+        auto: bool,
+        shell: Shell,
+    ) -> Result<()> {
+        if auto {
+            let url = match find_marker(&current_dir()?).await? {
+                Some(found) => match &found.marker.registry {
+                    Some(registry) => Some(self.resolve_registry_ref(registry)?),
+                    None => None,
+                },
+                None => None,
             };
+            let exported = env::var(HOOK_ENV_VAR).ok();
+            print!(
+                "{}",
+                shell.auto_env_script(url.as_deref(), exported.as_deref())
+            );
+            return Ok(());
+        }
 
-            if let Ok(current_dir) = env::current_dir() {
-                let absolute_path = if local {
-                    current_dir.join(&npmrc_path)
-                } else {
-                    Path::new(&npmrc_path).to_path_buf()
-                };
-                println!(
-                    "{} {}",
-                    format!("Absolute path of .npmrc:").blue().bold(),
-                    format!("{}", absolute_path.display())
-                );
+        let registry = registry.ok_or_else(|| {
+            Error::Validation("A registry name or --auto is required".to_string())
+        })?;
+        let url = &self.store.get_registry(&registry)?.registry;
+        print!("{}", shell.env_script(url));
+        Ok(())
+    }
+
+    fn handle_completions(&self, shell: CompletionShell) -> Result<()> {
+        print!("{}", completion_script(shell)?);
+        Ok(())
+    }
+
+    async fn handle_set_scope(
+        &mut self,
+        scope: String,
+        registry: String,
+        local: bool,
+        pm: Option<PackageManager>,
+    ) -> Result<()> {
+        let scope = parse_scope(&scope)?;
+        let registry_data = self.store.get_registry(&registry)?;
+
+        let mut targets = load_targets(pm, local).await?;
+        for target in targets.iter_mut() {
+            target.set_scope(&scope, &registry_data.registry);
+        }
+        save_targets(&targets).await?;
+
+        Logger::success(&format!(
+            "Scope {} now uses {} ({})",
+            scope.cyan().bold(),
+            registry.green().bold(),
+            if local { "local" } else { "global" }
+        ));
+        Ok(())
+    }
+
+    async fn handle_del_scope(
+        &mut self,
+        scope: String,
+        local: bool,
+        pm: Option<PackageManager>,
+    ) -> Result<()> {
+        let scope = parse_scope(&scope)?;
+
+        let mut targets = load_targets(pm, local).await?;
+        targets.retain_mut(|target| target.remove_scope(&scope));
+        if targets.is_empty() {
+            return Err(Error::NotFound(format!("Scope {}", scope)));
+        }
+        save_targets(&targets).await?;
+
+        Logger::success(&format!(
+            "Scope {} removed from {}",
+            scope.cyan().bold(),
+            target_names(&targets)
+        ));
+        Ok(())
+    }
+
+    async fn handle_set_auth(
+        &mut self,
+        registry: String,
+        auth: RegistryAuth,
+        local: bool,
+        pm: Option<PackageManager>,
+    ) -> Result<()> {
+        let registry_data = self.store.get_registry(&registry)?;
+
+        let mut targets = load_targets(pm, local).await?;
+        let several = targets.len() > 1;
+        let mut keys = Vec::new();
+        let mut skipped = None;
+        let mut updated = Vec::new();
+        for mut target in targets.drain(..) {
+            match target.set_auth(&registry_data.registry, &auth) {
+                Ok(key) => {
+                    keys.push(key);
+                    updated.push(target);
+                }
+                // With several targets, tools that cannot store the
+                // credentials are skipped instead of failing the command
+                Err(Error::Validation(reason)) if several => {
+                    Logger::info(&format!("Skipped {}: {}", target.package_manager(), reason));
+                    skipped.get_or_insert(reason);
+                }
+                Err(e) => return Err(e),
             }
+        }
+        if updated.is_empty() {
+            return Err(Error::Validation(skipped.unwrap_or_default()));
+        }
+        save_targets(&updated).await?;
 
-            if Path::new(&npmrc_path).exists() {
-                let content = read_to_string(&npmrc_path).await.unwrap();
-                let re = Regex::new(r"(?m)^\s*registry\s*=\s*.*$").unwrap();
-                let updated_content = re.replace_all(&content, &registry_text).to_string();
-                write(&npmrc_path, updated_content).await.unwrap();
-            } else {
-                write(&npmrc_path, registry_text).await.unwrap();
+        Logger::success(&format!(
+            "Credentials for {} saved under {} ({})",
+            registry.green().bold(),
+            keys.join(", ").yellow(),
+            auth.redacted()
+        ));
+        Ok(())
+    }
+
+    async fn handle_del_auth(
+        &mut self,
+        registry: String,
+        local: bool,
+        pm: Option<PackageManager>,
+    ) -> Result<()> {
+        let registry_data = self.store.get_registry(&registry)?;
+
+        let mut targets = load_targets(pm, local).await?;
+        let mut removed = 0;
+        targets.retain_mut(|target| {
+            let count = target.remove_auth(&registry_data.registry);
+            removed += count;
+            count > 0
+        });
+        if removed == 0 {
+            return Err(Error::NotFound(format!("Credentials for {}", registry)));
+        }
+        save_targets(&targets).await?;
+
+        Logger::success(&format!(
+            "Removed {} auth entries for {}",
+            removed,
+            registry.green().bold()
+        ));
+        Ok(())
+    }
+
+    async fn handle_current(&mut self, verbose: bool) -> Result<()> {
+        let report = self.store.current_report(&current_dir()?).await?;
+        match self.format {
+            OutputFormat::Json if verbose => return print_json(&report),
+            OutputFormat::Json | OutputFormat::Tsv => {
+                return print_record(self.format, Some(&report.record))
             }
+            OutputFormat::Text | OutputFormat::Toml | OutputFormat::Ini => {}
+        }
 
-            self.store.save().await;
-            Logger::success(&format!(
-                "{} registry updated!",
-                if local { "Local" } else { "Global" }
-            ));
+        let CurrentReport {
+            record,
+            source,
+            layers,
+        } = report;
+        let name = match &record.name {
+            Some(name) => name.green().bold(),
+            None => "unknown URL".red().bold(),
+        };
+        let yarn_tag = if record.active_yarn {
+            format!(" {}", "[YARN]".black().on_yellow())
         } else {
-            Logger::error("Registry not found!");
+            String::new()
+        };
+        println!("{} ({}){}", name, record.url.yellow(), yarn_tag);
+
+        // Yarn 1 reads its own `.yarnrc` first, so it may use another registry
+        let yarn_url = self.store.get_current_yarn_registry_url().await?;
+        if let Some(yarn_url) = yarn_url.filter(|_| !record.active_yarn) {
+            let yarn_name = match self.store.find_registry_name(&yarn_url) {
+                Some(name) => name.green().bold(),
+                None => "unknown URL".red().bold(),
+            };
+            println!(
+                "{} ({}) {}",
+                yarn_name,
+                yarn_url.yellow(),
+                "[YARN]".black().on_yellow()
+            );
+        }
+
+        if verbose {
+            Logger::line(&format!("{} {}", "Source:".blue().bold(), source));
+            for layer in &layers {
+                let marker = if layer.source == source {
+                    "✓".green()
+                } else {
+                    " ".normal()
+                };
+                let value = match &layer.url {
+                    Some(url) => url.yellow(),
+                    None => "not set".dimmed(),
+                };
+                Logger::line(&format!("  {} {} -> {}", marker, layer.source, value));
+            }
+        }
+        Ok(())
+    }
+
+    async fn handle_test(&mut self, args: TestArgs) -> Result<()> {
+        let tester = speed_tester(&args, &self.store.registries).await?;
+        let results = self.store.test_registry_speed(&tester).await?;
+        if args.record {
+            record_results(&results).await?;
         }
+        if !self.format.is_machine() {
+            return Ok(());
+        }
+
+        let current_global = self.store.get_current_registry(false).await?;
+        let current_local = self.store.get_current_registry(true).await?;
+        let current_yarn = self.store.get_current_yarn_registry().await?;
+        // Keep the ranking order of the results
+        let records: Vec<RegistryRecord> = results
+            .into_iter()
+            .enumerate()
+            .map(|(index, result)| {
+                let record = RegistryRecord::from_test_result(index + 1, result);
+                let name = record.name.as_ref();
+                RegistryRecord {
+                    home: name
+                        .and_then(|name| self.store.registries.get(name))
+                        .and_then(|registry| registry.home.clone()),
+                    active_global: current_global.as_ref() == name,
+                    active_local: current_local.as_ref() == name,
+                    active_yarn: current_yarn.as_ref() == name,
+                    ..record
+                }
+            })
+            .collect();
+
+        print_records(self.format, &records)
     }
 
-    async fn handle_test(&mut self) {
-        self.store.test_registry_speed().await;
+    async fn handle_history(
+        &mut self,
+        registry: Option<String>,
+        since: Option<Duration>,
+    ) -> Result<()> {
+        let path = history_path()?;
+        let cutoff = since.map(|since| history::now().saturating_sub(since.as_secs()));
+        let entries: Vec<HistoryEntry> = load_history(&path)
+            .await?
+            .into_iter()
+            .filter(|entry| registry.as_ref().is_none_or(|name| entry.name == *name))
+            .filter(|entry| cutoff.is_none_or(|cutoff| entry.timestamp >= cutoff))
+            .collect();
+        let trends = summarize(&entries);
+
+        if self.format.is_machine() {
+            return print_trends(self.format, &trends);
+        }
+        if trends.is_empty() {
+            Logger::info("No recorded speed tests, run `rust-nrm test --record` first");
+            return Ok(());
+        }
+
+        let name_width = trends
+            .iter()
+            .map(|trend| trend.name.len())
+            .chain(["Registry".len()])
+            .max()
+            .unwrap_or_default();
+        Logger::line(
+            &format!(
+                "{:<name_width$}  {:>5}  {:>8}  {:>8}  {:<12}  {}",
+                "Registry", "Runs", "Average", "Failures", "Trend", "History"
+            )
+            .bold()
+            .to_string(),
+        );
+        for trend in &trends {
+            let change = format!("{:<12}", trend.describe_change());
+            let change = if change.contains("slower") {
+                change.red()
+            } else if change.contains("faster") {
+                change.green()
+            } else {
+                change.normal()
+            };
+            Logger::line(&format!(
+                "{:<name_width$}  {:>5}  {:>8}  {:>7.0}%  {}  {}",
+                trend.name,
+                trend.runs,
+                trend
+                    .average_ms
+                    .map_or("-".to_string(), |ms| format!("{:.0}ms", ms)),
+                trend.failure_rate() * 100.0,
+                change,
+                trend.sparkline
+            ));
+        }
+        Ok(())
     }
 
-    async fn handle_add(&mut self, registry: String, url: String, home: Option<String>) {
+    async fn handle_add(
+        &mut self,
+        registry: String,
+        url: String,
+        home: Option<String>,
+        proxy: Option<String>,
+        force: bool,
+    ) -> Result<()> {
+        if self.store.registries.contains_key(&registry) && !force {
+            return Err(Error::Validation(format!(
+                "Registry {} already exists, use --force to overwrite it",
+                registry
+            )));
+        }
+        let url = normalize_registry_url(&url)?;
+        if let Some(home) = &home {
+            validate_homepage_url(home)?;
+        }
+        if let Some(proxy) = &proxy {
+            parse_proxy_url(proxy)?;
+        }
+
         self.store.registries.insert(
             registry.clone(),
             Registry {
                 registry: url.clone(),
                 home,
+                proxy,
+                ..Default::default()
             },
         );
 
-        self.store.save().await;
+        self.store.save().await?;
         Logger::success(&format!(
             "Registry {} added with URL: {}",
             registry.green().bold(),
             url.yellow()
         ));
+        Ok(())
+    }
+
+    async fn handle_export(&mut self, output: Option<PathBuf>) -> Result<()> {
+        let format = catalog_format(self.format)?
+            .or_else(|| output.as_deref().and_then(CatalogFormat::from_path))
+            .unwrap_or_default();
+        let content = export_registries(&self.store.registries, format)?;
+
+        match output {
+            Some(path) => {
+                write(&path, content)
+                    .await
+                    .map_err(|source| Error::ConfigIo {
+                        path: path.clone(),
+                        source,
+                    })?;
+                Logger::success(&format!(
+                    "Exported {} registries to {}",
+                    self.store.registries.len(),
+                    path.display()
+                ));
+            }
+            None => print!("{}", content),
+        }
+        Ok(())
+    }
+
+    async fn handle_import(
+        &mut self,
+        file: Option<PathBuf>,
+        from: Option<ImportSource>,
+        strategy: ConflictStrategy,
+        dry_run: bool,
+    ) -> Result<()> {
+        let source = from.unwrap_or(if file.is_some() {
+            ImportSource::File
+        } else {
+            ImportSource::Nrm
+        });
+        let path = file
+            .or_else(|| source.default_path())
+            .ok_or_else(|| Error::Validation("A file is required with --from file".to_string()))?;
+
+        let content = read_catalog(&path).await?;
+        let incoming = match source {
+            ImportSource::Nrm | ImportSource::Yrm => parse_ini_registries(&content)?,
+            ImportSource::File => parse_catalog(
+                &content,
+                catalog_format(self.format)?.or_else(|| CatalogFormat::from_path(&path)),
+            )?,
+        };
+        let incoming = normalize_registries(incoming)?;
+        Logger::info(&format!(
+            "Found {} registries in {}",
+            incoming.len(),
+            path.display()
+        ));
+
+        let mut merged = self.store.registries.clone();
+        let report = merge_registries(&mut merged, incoming, strategy);
+        print_merge_report(&report, &self.store.registries, &merged);
+
+        if dry_run {
+            Logger::info("Dry run, no changes were written");
+            return Ok(());
+        }
+        if report.has_changes() {
+            self.store.registries = merged;
+            self.store.save().await?;
+        }
+        Logger::success(&format!(
+            "Import finished: {} added, {} updated, {} renamed, {} skipped, {} unchanged",
+            report.added.len(),
+            report.updated.len(),
+            report.renamed.len(),
+            report.skipped.len() + report.conflicting.len(),
+            report.unchanged.len()
+        ));
+        Ok(())
     }
 
-    async fn handle_remove(&mut self, registry: String) {
-        if let Some(removed) = self.store.registries.remove(&registry) {
-            self.store.save().await;
-            Logger::success(&format!(
-                "Registry {} removed (URL: {})",
-                registry.green().bold(),
-                removed.registry.yellow()
+    async fn handle_remove(&mut self, registry: String, force: bool) -> Result<()> {
+        self.store.get_registry(&registry)?;
+        if Store::is_builtin(&registry) && !force {
+            return Err(Error::Validation(format!(
+                "Registry {} is built-in, use --force to remove it",
+                registry
+            )));
+        }
+
+        let removed = self
+            .store
+            .registries
+            .remove(&registry)
+            .ok_or_else(|| Error::NotFound(format!("Registry {}", registry)))?;
+
+        self.store.save().await?;
+        Logger::success(&format!(
+            "Registry {} removed (URL: {})",
+            registry.green().bold(),
+            removed.registry.yellow()
+        ));
+        Ok(())
+    }
+}
+
+/// Catalog format chosen with `--format`, `None` for the default `text`
+fn catalog_format(format: OutputFormat) -> Result<Option<CatalogFormat>> {
+    match format {
+        OutputFormat::Text => Ok(None),
+        OutputFormat::Toml => Ok(Some(CatalogFormat::Toml)),
+        OutputFormat::Json => Ok(Some(CatalogFormat::Json)),
+        OutputFormat::Ini => Ok(Some(CatalogFormat::Ini)),
+        OutputFormat::Tsv => Err(Error::Validation(
+            "--format tsv is not a catalog format, use toml, json or ini".to_string(),
+        )),
+    }
+}
+
+fn format_name(format: OutputFormat) -> String {
+    format
+        .to_possible_value()
+        .map(|value| value.get_name().to_string())
+        .unwrap_or_default()
+}
+
+/// Read a catalog file, or stdin for `-`
+async fn read_catalog(path: &Path) -> Result<String> {
+    let io_error = |source: std::io::Error| match source.kind() {
+        std::io::ErrorKind::NotFound => Error::NotFound(format!("File {}", path.display())),
+        _ => Error::ConfigIo {
+            path: path.to_path_buf(),
+            source,
+        },
+    };
+
+    if path == Path::new("-") {
+        let mut content = String::new();
+        tokio::io::stdin()
+            .read_to_string(&mut content)
+            .await
+            .map_err(io_error)?;
+        Ok(content)
+    } else {
+        read_to_string(path).await.map_err(io_error)
+    }
+}
+
+/// Print the difference an import makes, before it is applied
+fn print_merge_report(
+    report: &MergeReport,
+    before: &HashMap<String, Registry>,
+    after: &HashMap<String, Registry>,
+) {
+    let url = |registries: &HashMap<String, Registry>, name: &str| {
+        registries
+            .get(name)
+            .map(|registry| registry.registry.clone())
+            .unwrap_or_default()
+    };
+
+    for name in &report.added {
+        Logger::line(&format!(
+            "{} {} -> {}",
+            "+".green().bold(),
+            name.bold(),
+            url(after, name).yellow()
+        ));
+    }
+    for name in &report.updated {
+        let (old, new) = (&before[name], &after[name]);
+        if old.registry == new.registry {
+            Logger::line(&format!(
+                "{} {} -> {} (metadata updated)",
+                "~".yellow().bold(),
+                name.bold(),
+                new.registry.yellow()
             ));
         } else {
-            Logger::error(&format!(
-                "Registry {} not found",
-                registry.red().bold()
+            Logger::line(&format!(
+                "{} {} -> {} (was {})",
+                "~".yellow().bold(),
+                name.bold(),
+                new.registry.yellow(),
+                old.registry.dimmed()
             ));
         }
     }
+    for (name, new_name) in &report.renamed {
+        Logger::line(&format!(
+            "{} {} imported as {} -> {}",
+            "+".green().bold(),
+            name.bold(),
+            new_name.bold(),
+            url(after, new_name).yellow()
+        ));
+    }
+    for name in &report.skipped {
+        Logger::line(&format!(
+            "{} {} already exists with a different URL (skipped)",
+            "!".red().bold(),
+            name.bold()
+        ));
+    }
+    for name in &report.conflicting {
+        Logger::line(&format!(
+            "{} {} already exists with different metadata (skipped)",
+            "!".red().bold(),
+            name.bold()
+        ));
+    }
+}
+
+/// Append speed test results to the history (`test --record`)
+async fn record_results(results: &[SpeedTestResult]) -> Result<()> {
+    let path = history_path()?;
+    let timestamp = history::now();
+    let entries: Vec<HistoryEntry> = results
+        .iter()
+        .map(|result| HistoryEntry::from_result(result, timestamp))
+        .collect();
+    append_history(&path, &entries).await?;
+    Logger::info(&format!("Recorded results in {}", path.display()));
+    Ok(())
+}
+
+fn parse_scope(scope: &str) -> Result<String> {
+    normalize_scope(scope).ok_or_else(|| Error::Validation(format!("Invalid scope: {}", scope)))
+}
+
+/// Speed tester configured from the `test` options. Requests go through
+/// `--proxy`, else each registry's own proxy, else the npm and environment
+/// proxy settings.
+async fn speed_tester(
+    args: &TestArgs,
+    registries: &HashMap<String, Registry>,
+) -> Result<SpeedTester> {
+    let mut proxy = load_proxy_config(&current_dir()?).await?;
+    match &args.proxy {
+        Some(url) => proxy = proxy.with_override(parse_proxy_url(url)?),
+        None => {
+            for registry in registries.values() {
+                if let Some(url) = &registry.proxy {
+                    proxy = proxy.with_registry_proxy(&registry.registry, parse_proxy_url(url)?)?;
+                }
+            }
+        }
+    }
+
+    // The --detailed client only talks to http proxies, so fail before
+    // every sample does
+    if args.detailed {
+        let mut names: Vec<&String> = registries.keys().collect();
+        names.sort();
+        for name in names {
+            let unsupported = url::Url::parse(&registries[name].registry)
+                .ok()
+                .and_then(|url| proxy.proxy_for(&url))
+                .filter(|proxy| proxy.scheme() != "http");
+            if let Some(unsupported) = unsupported {
+                return Err(Error::Validation(format!(
+                    "--detailed only supports http:// proxies, but {} goes through a {} proxy at {}",
+                    name,
+                    unsupported.scheme(),
+                    unsupported.host_str().unwrap_or_default()
+                )));
+            }
+        }
+    }
+
+    let config = SpeedTesterConfig {
+        timeout: Duration::from_millis(args.timeout),
+        concurrency: args.concurrency as usize,
+        retries: args.retries,
+        proxy,
+        ..Default::default()
+    };
+    let mut tester = SpeedTester::with_config(config)?
+        .with_samples(args.samples as usize)
+        .with_warmup(args.warmup as usize)
+        .with_detailed(args.detailed);
+    if let Some(package) = &args.package {
+        tester = tester.with_package(PackageSpec::parse(package)?);
+    }
+    Ok(tester)
+}
+
+/// Config files of the requested package manager, or of every detected one
+/// for `all`. By default local changes go to the package manager detected
+/// for the current project and global ones to the npm user config, which
+/// does not depend on the directory the command runs in.
+async fn load_targets(
+    pm: Option<PackageManager>,
+    local: bool,
+) -> Result<Vec<Box<dyn RegistryTarget>>> {
+    load_targets_in(pm, local, &project_dir()?).await
+}
+
+/// Like `load_targets`, for the project at `root`: detection runs there and
+/// local configs are read from it
+async fn load_targets_in(
+    pm: Option<PackageManager>,
+    local: bool,
+    root: &Path,
+) -> Result<Vec<Box<dyn RegistryTarget>>> {
+    let pms = match pm {
+        Some(PackageManager::All) => PackageManager::detect_all(root).await,
+        Some(pm) => vec![pm],
+        None if local => vec![PackageManager::detect(root).await],
+        None => vec![PackageManager::Npm],
+    };
+
+    let mut targets = Vec::with_capacity(pms.len());
+    for pm in pms {
+        let path = if local {
+            local_target_path(pm, root)?
+        } else {
+            target_path(pm, false)?
+        };
+        targets.push(load_target_at(pm, path).await?);
+    }
+    Ok(targets)
+}
+
+async fn save_targets(targets: &[Box<dyn RegistryTarget>]) -> Result<()> {
+    for target in targets {
+        save_target(target.as_ref()).await?;
+    }
+    Ok(())
+}
+
+fn target_names(targets: &[Box<dyn RegistryTarget>]) -> String {
+    targets
+        .iter()
+        .map(|target| target.package_manager().to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn log_config_path(path: &Path) {
+    if let Ok(current_dir) = env::current_dir() {
+        let file_name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        Logger::line(&format!(
+            "{} {}",
+            format!("Absolute path of {}:", file_name).blue().bold(),
+            current_dir.join(path).display()
+        ));
+    }
 }
@@ -0,0 +1,72 @@
+use std::io;
+use std::path::PathBuf;
+use thiserror::Error;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Failed to access config file {}: {source}", path.display())]
+    ConfigIo {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+
+    #[error("Failed to parse config file {}: {message}", path.display())]
+    ConfigParse { path: PathBuf, message: String },
+
+    #[error("Failed to access {}: {source}", path.display())]
+    Npmrc {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+
+    /// I/O on a package manager config that is not an npmrc: `.yarnrc`,
+    /// `.yarnrc.yml` or `bunfig.toml`
+    #[error("Failed to access package manager config {}: {source}", path.display())]
+    PackageManagerConfig {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+
+    /// I/O on anything that is not a config file, such as the working
+    /// directory or the speed test history
+    #[error("Failed to access {}: {source}", path.display())]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+
+    #[error("Network error: {0}")]
+    Network(#[from] reqwest::Error),
+
+    #[error("{0} not found")]
+    NotFound(String),
+
+    #[error("{0}")]
+    Validation(String),
+
+    #[error("Failed to serialize output: {0}")]
+    Serialize(#[from] serde_json::Error),
+}
+
+impl Error {
+    /// Process exit code used by the binary; 1 is left to unknown failures,
+    /// 2 to clap usage errors (panics exit with 101)
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::NotFound(_) => 3,
+            Error::Validation(_) => 4,
+            Error::ConfigIo { .. } => 5,
+            Error::ConfigParse { .. } => 6,
+            Error::Npmrc { .. } | Error::PackageManagerConfig { .. } => 7,
+            Error::Network(_) => 8,
+            Error::Serialize(_) => 9,
+            Error::Io { .. } => 10,
+        }
+    }
+}
@@ -0,0 +1,238 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::env;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::fs::{self, OpenOptions};
+use tokio::io::AsyncWriteExt;
+
+use super::error::{Error, Result};
+use super::speed_test::{FailureKind, SpeedTestResult};
+
+/// Environment variable overriding where `test --record` stores its results
+pub const HISTORY_ENV_VAR: &str = "RUST_NRM_HISTORY";
+
+/// Bars of the sparkline, from fastest to slowest
+const SPARK_BARS: &[char] = &['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+/// Sparkline mark for a run where every request failed
+const SPARK_FAILURE: char = '×';
+/// Runs shown in a sparkline, the most recent ones
+const SPARK_WIDTH: usize = 30;
+/// Changes below this share of the older average count as steady
+const STEADY_THRESHOLD: f64 = 0.05;
+
+/// One registry's result in one recorded `test` run, stored as a JSON line
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    /// Unix time of the run, in seconds
+    pub timestamp: u64,
+    pub name: String,
+    pub url: String,
+    pub success: bool,
+    /// Median response time, `None` when every request failed
+    pub response_time_ms: Option<f64>,
+    /// Stage the test stopped at when it failed
+    pub error_kind: Option<FailureKind>,
+}
+
+impl HistoryEntry {
+    pub fn from_result(result: &SpeedTestResult, timestamp: u64) -> Self {
+        HistoryEntry {
+            timestamp,
+            name: result.name.clone(),
+            url: result.url.clone(),
+            success: result.is_success,
+            response_time_ms: Some(result.response_time * 1000.0).filter(|_| result.is_success),
+            error_kind: result
+                .failure
+                .as_ref()
+                .map(|failure| failure.kind)
+                .filter(|_| !result.is_success),
+        }
+    }
+}
+
+/// How one registry did over the recorded runs
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RegistryTrend {
+    pub name: String,
+    pub runs: usize,
+    pub failures: usize,
+    /// Mean of the successful runs' median response times
+    pub average_ms: Option<f64>,
+    /// Relative change from the older half of the successful runs to the
+    /// newer half; positive means slower. `None` below two successful runs.
+    pub change: Option<f64>,
+    /// One bar per run, oldest first, `×` for failed runs
+    pub sparkline: String,
+}
+
+impl RegistryTrend {
+    /// Share of the runs that failed, from 0.0 to 1.0
+    pub fn failure_rate(&self) -> f64 {
+        if self.runs == 0 {
+            0.0
+        } else {
+            self.failures as f64 / self.runs as f64
+        }
+    }
+
+    /// `+12% slower`, `-8% faster` or `steady`
+    pub fn describe_change(&self) -> String {
+        match self.change {
+            Some(change) if change >= STEADY_THRESHOLD => {
+                format!("+{:.0}% slower", change * 100.0)
+            }
+            Some(change) if change <= -STEADY_THRESHOLD => {
+                format!("{:.0}% faster", change * 100.0)
+            }
+            Some(_) => "steady".to_string(),
+            None => "-".to_string(),
+        }
+    }
+}
+
+/// Resolve the history file location.
+///
+/// Precedence: `RUST_NRM_HISTORY`, then `rust-nrm/history.jsonl` in the
+/// user data directory (`~/.local/share` on Linux).
+pub fn history_path() -> Result<PathBuf> {
+    if let Some(path) = env::var_os(HISTORY_ENV_VAR).filter(|path| !path.is_empty()) {
+        return Ok(PathBuf::from(path));
+    }
+    let data_dir = dirs::data_dir().ok_or_else(|| Error::NotFound("Data directory".to_string()))?;
+    Ok(data_dir.join("rust-nrm").join("history.jsonl"))
+}
+
+/// Seconds since the Unix epoch
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+/// Append `entries` to the history file, creating it if needed
+pub async fn append_history(path: &Path, entries: &[HistoryEntry]) -> Result<()> {
+    let io_error = |source| Error::Io {
+        path: path.to_path_buf(),
+        source,
+    };
+    if let Some(parent) = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        fs::create_dir_all(parent).await.map_err(io_error)?;
+    }
+
+    let mut lines = String::new();
+    for entry in entries {
+        lines.push_str(&serde_json::to_string(entry)?);
+        lines.push('\n');
+    }
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .await
+        .map_err(io_error)?;
+    file.write_all(lines.as_bytes()).await.map_err(io_error)
+}
+
+/// Every entry of the history file, empty when nothing was recorded yet.
+/// Lines that do not parse, such as one cut short by a crash, are skipped.
+pub async fn load_history(path: &Path) -> Result<Vec<HistoryEntry>> {
+    let content = match fs::read_to_string(path).await {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(source) => {
+            return Err(Error::Io {
+                path: path.to_path_buf(),
+                source,
+            })
+        }
+    };
+    Ok(content
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect())
+}
+
+/// Parse an age such as `90m`, `12h`, `7d` or `2w`
+pub fn parse_age(value: &str) -> std::result::Result<Duration, String> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (amount, unit) = value.split_at(split);
+    let amount: u64 = amount.parse().map_err(|_| {
+        format!(
+            "expected a number followed by s, m, h, d or w, got `{}`",
+            value
+        )
+    })?;
+    let seconds = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "" | "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => return Err(format!("unknown unit `{}`, expected s, m, h, d or w", unit)),
+    };
+    Ok(Duration::from_secs(amount.saturating_mul(seconds)))
+}
+
+/// Per-registry trends of `entries`, sorted by registry name
+pub fn summarize(entries: &[HistoryEntry]) -> Vec<RegistryTrend> {
+    let mut by_name: BTreeMap<&str, Vec<&HistoryEntry>> = BTreeMap::new();
+    for entry in entries {
+        by_name.entry(&entry.name).or_default().push(entry);
+    }
+
+    by_name
+        .into_iter()
+        .map(|(name, mut runs)| {
+            runs.sort_by_key(|entry| entry.timestamp);
+            let times: Vec<f64> = runs
+                .iter()
+                .filter_map(|entry| entry.response_time_ms.filter(|_| entry.success))
+                .collect();
+            let average = |times: &[f64]| times.iter().sum::<f64>() / times.len() as f64;
+
+            let change = (times.len() >= 2).then(|| {
+                let (older, newer) = times.split_at(times.len() / 2);
+                (average(newer) - average(older)) / average(older)
+            });
+
+            RegistryTrend {
+                name: name.to_string(),
+                runs: runs.len(),
+                failures: runs.iter().filter(|entry| !entry.success).count(),
+                average_ms: (!times.is_empty()).then(|| average(&times)),
+                change,
+                sparkline: sparkline(&runs[runs.len().saturating_sub(SPARK_WIDTH)..]),
+            }
+        })
+        .collect()
+}
+
+/// One bar per run scaled between the fastest and slowest of them
+fn sparkline(runs: &[&HistoryEntry]) -> String {
+    let times = runs
+        .iter()
+        .filter_map(|entry| entry.response_time_ms.filter(|_| entry.success));
+    let min = times.clone().fold(f64::INFINITY, f64::min);
+    let max = times.fold(f64::NEG_INFINITY, f64::max);
+
+    runs.iter()
+        .map(
+            |entry| match entry.response_time_ms.filter(|_| entry.success) {
+                Some(time) if max > min => {
+                    let level = (time - min) / (max - min) * (SPARK_BARS.len() - 1) as f64;
+                    SPARK_BARS[level.round() as usize]
+                }
+                Some(_) => SPARK_BARS[0],
+                None => SPARK_FAILURE,
+            },
+        )
+        .collect()
+}
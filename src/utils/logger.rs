@@ -1,77 +1,141 @@
 use colored::Colorize;
+use std::io::{self, IsTerminal, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// When set, log chatter goes to stderr so stdout only carries command output
+static USE_STDERR: AtomicBool = AtomicBool::new(false);
+/// When set, INFO lines are dropped (errors are always shown)
+static QUIET: AtomicBool = AtomicBool::new(false);
 
 pub struct Logger;
 
 impl Logger {
+    pub fn set_stderr(enabled: bool) {
+        USE_STDERR.store(enabled, Ordering::Relaxed);
+    }
+
+    pub fn set_quiet(enabled: bool) {
+        QUIET.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Print an unlabeled line to the log stream
+    pub fn line(message: &str) {
+        if USE_STDERR.load(Ordering::Relaxed) {
+            eprintln!("{}", message);
+        } else {
+            println!("{}", message);
+        }
+    }
+
+    /// Whether the log stream is a terminal, so lines can be redrawn in place
+    pub fn is_terminal() -> bool {
+        if USE_STDERR.load(Ordering::Relaxed) {
+            io::stderr().is_terminal()
+        } else {
+            io::stdout().is_terminal()
+        }
+    }
+
+    /// Replace the last `previous` lines of the log stream with `lines`
+    pub fn redraw(previous: usize, lines: &[String]) {
+        let mut output = String::new();
+        if previous > 0 {
+            output.push_str(&format!("\x1b[{}A", previous));
+        }
+        output.push_str("\x1b[J");
+        for line in lines {
+            output.push_str(line);
+            output.push('\n');
+        }
+        if USE_STDERR.load(Ordering::Relaxed) {
+            let mut stderr = io::stderr().lock();
+            let _ = stderr
+                .write_all(output.as_bytes())
+                .and_then(|_| stderr.flush());
+        } else {
+            let mut stdout = io::stdout().lock();
+            let _ = stdout
+                .write_all(output.as_bytes())
+                .and_then(|_| stdout.flush());
+        }
+    }
+
     pub fn info(message: &str) {
-        println!(
+        if QUIET.load(Ordering::Relaxed) {
+            return;
+        }
+        Self::line(&format!(
             "{} {}",
-            format!(" INFO ").white().on_blue(),
+            " INFO ".white().on_blue(),
             message.blue()
-        );
+        ));
     }
 
     pub fn info_bold(message: &str) {
-        println!(
+        if QUIET.load(Ordering::Relaxed) {
+            return;
+        }
+        Self::line(&format!(
             "{} {}",
-            format!(" INFO ").white().on_blue(),
+            " INFO ".white().on_blue(),
             message.blue().bold()
-        );
+        ));
     }
 
     pub fn success(message: &str) {
-        println!(
+        Self::line(&format!(
             "{} {}",
-            format!(" SUCCESS ").white().on_green(),
+            " SUCCESS ".white().on_green(),
             message.green()
-        );
+        ));
     }
 
     pub fn success_bold(message: &str) {
-        println!(
+        Self::line(&format!(
             "{} {}",
-            format!(" SUCCESS ").white().on_green(),
+            " SUCCESS ".white().on_green(),
             message.green().bold()
-        );
+        ));
     }
 
     pub fn error(message: &str) {
-        println!(
-            "{} {}",
-            format!(" ERROR ").white().on_red(),
-            message.red()
-        );
+        Self::line(&format!("{} {}", " ERROR ".white().on_red(), message.red()));
     }
 
     pub fn error_bold(message: &str) {
-        println!(
+        Self::line(&format!(
             "{} {}",
-            format!(" ERROR ").white().on_red(),
+            " ERROR ".white().on_red(),
             message.red().bold()
-        );
+        ));
     }
 
     pub fn list(message: &str) {
-        println!(
+        Self::line(&format!(
             "{} {}",
-            format!(" LIST ").white().on_magenta(),
+            " LIST ".white().on_magenta(),
             message.magenta().bold()
-        );
+        ));
     }
 
     pub fn custom(label: &str, message: &str, bg_color: colored::Color, fg_color: colored::Color) {
-        println!(
+        Self::line(&format!(
             "{} {}",
             format!(" {} ", label).white().on_color(bg_color),
             message.color(fg_color)
-        );
+        ));
     }
 
-    pub fn custom_bold(label: &str, message: &str, bg_color: colored::Color, fg_color: colored::Color) {
-        println!(
+    pub fn custom_bold(
+        label: &str,
+        message: &str,
+        bg_color: colored::Color,
+        fg_color: colored::Color,
+    ) {
+        Self::line(&format!(
             "{} {}",
             format!(" {} ", label).white().on_color(bg_color),
             message.color(fg_color).bold()
-        );
+        ));
     }
 }
@@ -0,0 +1,165 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use tokio::fs;
+use toml_edit::{value, DocumentMut};
+
+use super::error::{Error, Result};
+
+/// Marker with a registry and optional `[scopes]` table
+pub const TOML_MARKER: &str = "rust-nrm.toml";
+/// `.nvmrc`-style marker holding just a registry name
+pub const PLAIN_MARKER: &str = ".nrmrc";
+
+/// Registry settings a project asks for.
+///
+/// Values are stored registry names, or registry URLs so a committed marker
+/// works for people who named their registries differently.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ProjectMarker {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub registry: Option<String>,
+    /// `@scope` to registry name or URL
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub scopes: BTreeMap<String, String>,
+}
+
+impl ProjectMarker {
+    pub fn is_empty(&self) -> bool {
+        self.registry.is_none() && self.scopes.is_empty()
+    }
+}
+
+/// A marker file found on disk
+#[derive(Debug, Clone, PartialEq)]
+pub struct FoundMarker {
+    pub path: PathBuf,
+    pub marker: ProjectMarker,
+}
+
+impl FoundMarker {
+    /// Directory the marker applies to
+    pub fn dir(&self) -> &Path {
+        self.path.parent().unwrap_or_else(|| Path::new("."))
+    }
+}
+
+/// Walk up from `cwd` to the nearest directory with a marker.
+/// `rust-nrm.toml` wins over `.nrmrc` in the same directory.
+pub async fn find_marker(cwd: &Path) -> Result<Option<FoundMarker>> {
+    for dir in cwd.ancestors() {
+        for file_name in [TOML_MARKER, PLAIN_MARKER] {
+            let path = dir.join(file_name);
+            if !path.is_file() {
+                continue;
+            }
+            let content = fs::read_to_string(&path)
+                .await
+                .map_err(|source| Error::ConfigIo {
+                    path: path.clone(),
+                    source,
+                })?;
+            if let Some(marker) = parse_marker(&path, &content)? {
+                return Ok(Some(FoundMarker { path, marker }));
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// Parse a marker file. Returns `None` for files that share the name but are
+/// not markers, such as the Node nrm's own `~/.nrmrc` registry list.
+pub fn parse_marker(path: &Path, content: &str) -> Result<Option<ProjectMarker>> {
+    let is_toml = path.file_name().is_some_and(|name| name == TOML_MARKER);
+
+    let marker = if is_toml {
+        toml::from_str(content).map_err(|e| Error::ConfigParse {
+            path: path.to_path_buf(),
+            message: e.to_string(),
+        })?
+    } else {
+        let Some(line) = content
+            .lines()
+            .map(str::trim)
+            .find(|line| !line.is_empty() && !line.starts_with('#'))
+        else {
+            return Ok(None);
+        };
+        if line.starts_with('[') || line.contains('=') {
+            return Ok(None);
+        }
+        ProjectMarker {
+            registry: Some(line.to_string()),
+            ..Default::default()
+        }
+    };
+    Ok((!marker.is_empty()).then_some(marker))
+}
+
+/// Pin `registry` for the project in `dir`, updating an existing `.nrmrc`
+/// or `rust-nrm.toml` and creating `rust-nrm.toml` otherwise. Only the
+/// registry line changes; comments and other settings are kept.
+pub async fn pin_marker(dir: &Path, registry: &str) -> Result<PathBuf> {
+    let toml_path = dir.join(TOML_MARKER);
+    let plain_path = dir.join(PLAIN_MARKER);
+    if !toml_path.exists() {
+        if let Ok(content) = fs::read_to_string(&plain_path).await {
+            if parse_marker(&plain_path, &content)?.is_some() {
+                let content = replace_plain_registry(&content, registry);
+                return match fs::write(&plain_path, content).await {
+                    Ok(()) => Ok(plain_path),
+                    Err(source) => Err(Error::ConfigIo {
+                        path: plain_path,
+                        source,
+                    }),
+                };
+            }
+        }
+    }
+
+    let content = match fs::read_to_string(&toml_path).await {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(source) => {
+            return Err(Error::ConfigIo {
+                path: toml_path,
+                source,
+            })
+        }
+    };
+    let mut doc = content
+        .parse::<DocumentMut>()
+        .map_err(|e| Error::ConfigParse {
+            path: toml_path.clone(),
+            message: e.to_string(),
+        })?;
+    doc["registry"] = value(registry);
+
+    match fs::write(&toml_path, doc.to_string()).await {
+        Ok(()) => Ok(toml_path),
+        Err(source) => Err(Error::ConfigIo {
+            path: toml_path,
+            source,
+        }),
+    }
+}
+
+/// Swap the registry line of a `.nrmrc`, the first one that is not blank or
+/// a comment, keeping every other line
+fn replace_plain_registry(content: &str, registry: &str) -> String {
+    let mut replaced = false;
+    let lines: Vec<&str> = content
+        .lines()
+        .map(|line| {
+            let trimmed = line.trim();
+            if replaced || trimmed.is_empty() || trimmed.starts_with('#') {
+                line
+            } else {
+                replaced = true;
+                registry
+            }
+        })
+        .collect();
+    lines.join("\n") + "\n"
+}
@@ -1,6 +1,21 @@
+pub mod auth;
+pub mod catalog;
 pub mod cli;
+pub mod error;
+pub mod history;
 pub mod logger;
+pub mod marker;
+pub mod npmrc;
+pub mod output;
+pub mod package_manager;
+pub mod proxy;
 pub mod registries;
+pub mod resolve;
+pub mod shell;
 pub mod speed_test;
+pub mod targets;
+pub mod timing;
+pub mod yarnrc;
 
+pub use error::{Error, Result};
 pub use logger::Logger;
@@ -0,0 +1,415 @@
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use tokio::fs;
+
+use super::error::{Error, Result};
+use super::resolve::project_dir;
+
+/// A single `key=value` line of an `.npmrc` file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NpmrcEntry {
+    pub key: String,
+    pub value: String,
+    /// `true` for `key[]=value` array entries
+    pub is_array: bool,
+    /// Original text of the line, kept until the entry is modified
+    raw: Option<String>,
+}
+
+impl NpmrcEntry {
+    fn new(key: &str, value: &str, is_array: bool) -> Self {
+        Self {
+            key: key.to_string(),
+            value: value.to_string(),
+            is_array,
+            raw: None,
+        }
+    }
+
+    /// Value with `${VAR}` references replaced from the environment
+    pub fn expanded_value(&self) -> String {
+        expand_env(&self.value)
+    }
+
+    fn render(&self) -> String {
+        if let Some(raw) = &self.raw {
+            return raw.clone();
+        }
+        let suffix = if self.is_array { "[]" } else { "" };
+        format!("{}{}={}", self.key, suffix, quote_value(&self.value))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum NpmrcLine {
+    Blank(String),
+    Comment(String),
+    Section(String),
+    Entry(NpmrcEntry),
+}
+
+impl NpmrcLine {
+    fn parse(raw: &str) -> Self {
+        let trimmed = raw.trim();
+        if trimmed.is_empty() {
+            return NpmrcLine::Blank(raw.to_string());
+        }
+        if trimmed.starts_with('#') || trimmed.starts_with(';') {
+            return NpmrcLine::Comment(raw.to_string());
+        }
+        if trimmed.starts_with('[') && trimmed.ends_with(']') {
+            return NpmrcLine::Section(raw.to_string());
+        }
+
+        // A bare key without `=` is treated as `key=true`, like npm's ini parser
+        let (key, value) = match trimmed.split_once('=') {
+            Some((key, value)) => (key.trim(), unquote_value(value.trim())),
+            None => (trimmed, "true".to_string()),
+        };
+        let (key, is_array) = match key.strip_suffix("[]") {
+            Some(key) => (key, true),
+            None => (key, false),
+        };
+
+        NpmrcLine::Entry(NpmrcEntry {
+            key: key.to_string(),
+            value,
+            is_array,
+            raw: Some(raw.to_string()),
+        })
+    }
+
+    fn render(&self) -> String {
+        match self {
+            NpmrcLine::Blank(raw) | NpmrcLine::Comment(raw) | NpmrcLine::Section(raw) => {
+                raw.clone()
+            }
+            NpmrcLine::Entry(entry) => entry.render(),
+        }
+    }
+}
+
+/// Ordered, lossless representation of an `.npmrc` file.
+///
+/// Lines that are not touched through `set`/`remove` are written back
+/// byte-for-byte, so comments, scoped registries and auth lines survive edits.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Npmrc {
+    lines: Vec<NpmrcLine>,
+    crlf: bool,
+    trailing_newline: bool,
+}
+
+impl Default for Npmrc {
+    fn default() -> Self {
+        Self {
+            lines: Vec::new(),
+            crlf: false,
+            trailing_newline: true,
+        }
+    }
+}
+
+impl Npmrc {
+    pub fn parse(content: &str) -> Self {
+        let crlf = content.contains("\r\n");
+        let trailing_newline = content.is_empty() || content.ends_with('\n');
+        let body = content.strip_suffix('\n').unwrap_or(content);
+
+        let lines = if content.is_empty() {
+            Vec::new()
+        } else {
+            body.split('\n')
+                .map(|line| NpmrcLine::parse(line.strip_suffix('\r').unwrap_or(line)))
+                .collect()
+        };
+
+        Self {
+            lines,
+            crlf,
+            trailing_newline,
+        }
+    }
+
+    /// Read an `.npmrc` file, returning an empty document if it does not exist
+    pub async fn load(path: &Path) -> Result<Self> {
+        match fs::read_to_string(path).await {
+            Ok(content) => Ok(Self::parse(&content)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(source) => Err(Error::Npmrc {
+                path: path.to_path_buf(),
+                source,
+            }),
+        }
+    }
+
+    pub async fn save(&self, path: &Path) -> Result<()> {
+        fs::write(path, self.to_string())
+            .await
+            .map_err(|source| Error::Npmrc {
+                path: path.to_path_buf(),
+                source,
+            })
+    }
+
+    pub fn lines(&self) -> &[NpmrcLine] {
+        &self.lines
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = &NpmrcEntry> {
+        self.lines.iter().filter_map(|line| match line {
+            NpmrcLine::Entry(entry) => Some(entry),
+            _ => None,
+        })
+    }
+
+    /// Value of a plain (non-array) key; the last occurrence wins, as in npm
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries()
+            .filter(|entry| !entry.is_array && entry.key == key)
+            .last()
+            .map(|entry| entry.value.as_str())
+    }
+
+    /// Value of a key with `${VAR}` references expanded
+    pub fn get_expanded(&self, key: &str) -> Option<String> {
+        self.get(key).map(expand_env)
+    }
+
+    /// All values of a `key[]=` array
+    pub fn get_all(&self, key: &str) -> Vec<&str> {
+        self.entries()
+            .filter(|entry| entry.is_array && entry.key == key)
+            .map(|entry| entry.value.as_str())
+            .collect()
+    }
+
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.entries().any(|entry| entry.key == key)
+    }
+
+    /// Set a plain key, updating the first occurrence in place and dropping
+    /// any later duplicates. New keys are appended before the first section.
+    pub fn set(&mut self, key: &str, value: &str) {
+        let mut found = false;
+        self.lines.retain_mut(|line| match line {
+            NpmrcLine::Entry(entry) if !entry.is_array && entry.key == key => {
+                if found {
+                    return false;
+                }
+                found = true;
+                if entry.value != value {
+                    entry.value = value.to_string();
+                    entry.raw = None;
+                }
+                true
+            }
+            _ => true,
+        });
+
+        if !found {
+            let entry = NpmrcLine::Entry(NpmrcEntry::new(key, value, false));
+            let position = self
+                .lines
+                .iter()
+                .position(|line| matches!(line, NpmrcLine::Section(_)))
+                .unwrap_or(self.lines.len());
+            self.lines.insert(position, entry);
+        }
+    }
+
+    /// Replace every `key[]=` entry with the given values
+    pub fn set_all(&mut self, key: &str, values: &[&str]) {
+        let position = self.lines.iter().position(
+            |line| matches!(line, NpmrcLine::Entry(entry) if entry.is_array && entry.key == key),
+        );
+        self.lines.retain(
+            |line| !matches!(line, NpmrcLine::Entry(entry) if entry.is_array && entry.key == key),
+        );
+
+        let position = position.unwrap_or_else(|| {
+            self.lines
+                .iter()
+                .position(|line| matches!(line, NpmrcLine::Section(_)))
+                .unwrap_or(self.lines.len())
+        });
+        for (offset, value) in values.iter().enumerate() {
+            self.lines.insert(
+                position + offset,
+                NpmrcLine::Entry(NpmrcEntry::new(key, value, true)),
+            );
+        }
+    }
+
+    /// Remove every entry for `key`, returning whether anything was removed
+    pub fn remove(&mut self, key: &str) -> bool {
+        let before = self.lines.len();
+        self.lines
+            .retain(|line| !matches!(line, NpmrcLine::Entry(entry) if entry.key == key));
+        self.lines.len() != before
+    }
+
+    /// `(scope, url)` pairs for every `@scope:registry` entry, in file order
+    pub fn scopes(&self) -> Vec<(String, String)> {
+        self.entries()
+            .filter(|entry| !entry.is_array)
+            .filter_map(|entry| {
+                scope_from_key(&entry.key).map(|scope| (scope.to_string(), entry.expanded_value()))
+            })
+            .collect()
+    }
+
+    /// Remove every entry whose key satisfies the predicate
+    pub fn remove_matching<F: Fn(&str) -> bool>(&mut self, predicate: F) -> Vec<String> {
+        let mut removed = Vec::new();
+        self.lines.retain(|line| match line {
+            NpmrcLine::Entry(entry) if predicate(&entry.key) => {
+                removed.push(entry.key.clone());
+                false
+            }
+            _ => true,
+        });
+        removed
+    }
+}
+
+impl fmt::Display for Npmrc {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let newline = if self.crlf { "\r\n" } else { "\n" };
+        for (index, line) in self.lines.iter().enumerate() {
+            if index > 0 {
+                f.write_str(newline)?;
+            }
+            f.write_str(&line.render())?;
+        }
+        if !self.lines.is_empty() && self.trailing_newline {
+            f.write_str(newline)?;
+        }
+        Ok(())
+    }
+}
+
+/// Path of the global (`~/.npmrc`) or local config file. Like npm, the
+/// local `.npmrc` is the one at the project root (see `find_project_root`).
+///
+/// Like npm, the user config location can be overridden with
+/// `npm_config_userconfig`.
+pub fn npmrc_path(is_local: bool) -> Result<PathBuf> {
+    if is_local {
+        return Ok(project_dir()?.join(".npmrc"));
+    }
+
+    if let Some(path) = npm_env_config("userconfig") {
+        return Ok(PathBuf::from(path));
+    }
+
+    dirs::home_dir()
+        .map(|home| home.join(".npmrc"))
+        .ok_or_else(|| Error::NotFound("Home directory".to_string()))
+}
+
+/// Value of an `npm_config_<key>` environment variable; like npm, the
+/// variable name is matched case-insensitively
+pub fn npm_env_config(key: &str) -> Option<String> {
+    let name = format!("npm_config_{}", key.to_lowercase().replace('-', "_"));
+    std::env::vars_os()
+        .filter(|(var, _)| var.to_str().is_some_and(|var| var.to_lowercase() == name))
+        .find_map(|(_, value)| value.into_string().ok().filter(|value| !value.is_empty()))
+}
+
+/// Normalize a scope name to its `@scope` form, rejecting malformed names
+pub fn normalize_scope(scope: &str) -> Option<String> {
+    let name = scope.trim().trim_start_matches('@');
+    let valid = !name.is_empty()
+        && !name
+            .chars()
+            .any(|c| c.is_whitespace() || matches!(c, '/' | ':' | '@' | '=' | '[' | ']'));
+    valid.then(|| format!("@{}", name))
+}
+
+/// `.npmrc` key holding the registry of a normalized `@scope`
+pub fn scope_registry_key(scope: &str) -> String {
+    format!("{}:registry", scope)
+}
+
+/// Scope name of an `@scope:registry` key
+pub fn scope_from_key(key: &str) -> Option<&str> {
+    key.strip_suffix(":registry")
+        .filter(|scope| scope.starts_with('@') && scope.len() > 1)
+}
+
+/// Replace `${VAR}` references with environment values, leaving unknown ones as-is
+pub fn expand_env(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+    let mut rest = value;
+
+    while let Some(start) = rest.find("${") {
+        let escaped = start > 0 && rest.as_bytes()[start - 1] == b'\\';
+        let Some(end) = rest[start..].find('}') else {
+            break;
+        };
+        let name = &rest[start + 2..start + end];
+
+        if escaped {
+            result.push_str(&rest[..start - 1]);
+            result.push_str(&rest[start..=start + end]);
+        } else {
+            result.push_str(&rest[..start]);
+            match std::env::var(name) {
+                Ok(env_value) => result.push_str(&env_value),
+                Err(_) => result.push_str(&rest[start..=start + end]),
+            }
+        }
+        rest = &rest[start + end + 1..];
+    }
+
+    result.push_str(rest);
+    result
+}
+
+fn unquote_value(value: &str) -> String {
+    let bytes = value.as_bytes();
+    if value.len() >= 2 {
+        let (first, last) = (bytes[0], bytes[value.len() - 1]);
+        if first == b'"' && last == b'"' {
+            return value[1..value.len() - 1]
+                .replace("\\\"", "\"")
+                .replace("\\\\", "\\");
+        }
+        if first == b'\'' && last == b'\'' {
+            return value[1..value.len() - 1].to_string();
+        }
+    }
+
+    // Unquoted values end at the first unescaped inline comment
+    let mut result = String::with_capacity(value.len());
+    let mut chars = value.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if matches!(chars.peek(), Some(';') | Some('#')) => {
+                result.push(chars.next().unwrap());
+            }
+            ';' | '#' => break,
+            _ => result.push(c),
+        }
+    }
+    result.trim().to_string()
+}
+
+/// Mirrors npm's ini `safe()`: quote values that would not round-trip,
+/// otherwise escape inline comment characters
+fn quote_value(value: &str) -> String {
+    let is_quoted = value.len() > 1
+        && ((value.starts_with('"') && value.ends_with('"'))
+            || (value.starts_with('\'') && value.ends_with('\'')));
+    let needs_quotes = value.contains(['=', '\r', '\n'])
+        || value.starts_with('[')
+        || is_quoted
+        || value.trim() != value;
+    if needs_quotes {
+        format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+    } else {
+        value.replace(';', "\\;").replace('#', "\\#")
+    }
+}
@@ -0,0 +1,239 @@
+use clap::ValueEnum;
+use serde::Serialize;
+
+use super::error::Result;
+use super::history::RegistryTrend;
+use super::resolve::{RegistryLayer, RegistrySource};
+use super::speed_test::{FailureKind, SpeedTestResult};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Colored, human-readable output
+    #[default]
+    Text,
+    /// One JSON document on stdout
+    Json,
+    /// Tab-separated values with a header row
+    Tsv,
+    /// Registry catalog as TOML (`export` and `import` only)
+    Toml,
+    /// Registry catalog in nrm's ini layout (`export` and `import` only)
+    Ini,
+}
+
+impl OutputFormat {
+    /// Machine formats keep stdout free of log chatter
+    pub fn is_machine(&self) -> bool {
+        !matches!(self, OutputFormat::Text)
+    }
+
+    /// Formats that only describe a registry catalog
+    pub fn is_catalog_only(&self) -> bool {
+        matches!(self, OutputFormat::Toml | OutputFormat::Ini)
+    }
+}
+
+/// Stable record emitted by `ls`, `test` and `current` in JSON/TSV mode.
+///
+/// Every field is always present; values that do not apply to a command are
+/// `null` (empty in TSV).
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct RegistryRecord {
+    /// Stored registry name, `null` if the URL is not in the config
+    pub name: Option<String>,
+    pub url: String,
+    pub home: Option<String>,
+    pub active_global: bool,
+    pub active_local: bool,
+    /// Set as `registry` in the Yarn 1 `.yarnrc`
+    pub active_yarn: bool,
+    pub response_time_ms: Option<f64>,
+    pub success: Option<bool>,
+    /// Time to the first response byte (`test --package` and `--detailed` only)
+    pub ttfb_ms: Option<f64>,
+    /// Tarball download speed in MB/s (`test --package` only)
+    pub throughput_mbps: Option<f64>,
+    /// HTTP status of the last response (`test` only)
+    pub status: Option<u16>,
+    /// Stage a failed test stopped at (`test` only)
+    pub error_kind: Option<FailureKind>,
+    /// Why the test failed (`test` only)
+    pub error: Option<String>,
+    /// Host name resolution (`test --detailed` only)
+    pub dns_ms: Option<f64>,
+    /// TCP connect (`test --detailed` only)
+    pub connect_ms: Option<f64>,
+    /// TLS handshake, `null` for http (`test --detailed` only)
+    pub tls_ms: Option<f64>,
+    /// Response body transfer (`test --detailed` only)
+    pub body_ms: Option<f64>,
+    /// Position in the speed ranking, from 1 (`test` only)
+    pub rank: Option<usize>,
+}
+
+/// `current --verbose` JSON: the record plus where the registry came from
+#[derive(Debug, Clone, Serialize)]
+pub struct CurrentReport {
+    #[serde(flatten)]
+    pub record: RegistryRecord,
+    pub source: RegistrySource,
+    pub layers: Vec<RegistryLayer>,
+}
+
+const TSV_HEADER: &str =
+    "name\turl\thome\tactive_global\tactive_local\tactive_yarn\tresponse_time_ms\tsuccess\tttfb_ms\tthroughput_mbps\tstatus\terror_kind\terror\tdns_ms\tconnect_ms\ttls_ms\tbody_ms\trank";
+
+impl RegistryRecord {
+    /// Record for the `rank`-th fastest registry of a `test` run
+    pub fn from_test_result(rank: usize, result: SpeedTestResult) -> Self {
+        // Partial failures of a working registry only lower its success ratio
+        let failure = result.failure.filter(|_| !result.is_success);
+        RegistryRecord {
+            response_time_ms: Some(result.response_time * 1000.0),
+            success: Some(result.is_success),
+            ttfb_ms: match (result.phases, result.download) {
+                (Some(phases), _) => Some(phases.ttfb * 1000.0),
+                (None, Some(download)) => Some(download.ttfb * 1000.0),
+                (None, None) => None,
+            },
+            throughput_mbps: result.download.map(|download| download.throughput),
+            status: result.status,
+            error_kind: failure.as_ref().map(|failure| failure.kind),
+            error: failure.map(|failure| failure.message),
+            dns_ms: result.phases.map(|phases| phases.dns * 1000.0),
+            connect_ms: result.phases.map(|phases| phases.connect * 1000.0),
+            tls_ms: result
+                .phases
+                .and_then(|phases| phases.tls)
+                .map(|tls| tls * 1000.0),
+            body_ms: result.phases.map(|phases| phases.body * 1000.0),
+            rank: Some(rank),
+            name: Some(result.name),
+            url: result.url,
+            ..Default::default()
+        }
+    }
+
+    fn tsv_row(&self) -> String {
+        let optional = |value: Option<String>| value.unwrap_or_default();
+        [
+            optional(self.name.clone()),
+            self.url.clone(),
+            optional(self.home.clone()),
+            self.active_global.to_string(),
+            self.active_local.to_string(),
+            self.active_yarn.to_string(),
+            optional(self.response_time_ms.map(|ms| format!("{:.0}", ms))),
+            optional(self.success.map(|success| success.to_string())),
+            optional(self.ttfb_ms.map(|ms| format!("{:.0}", ms))),
+            optional(self.throughput_mbps.map(|mbps| format!("{:.2}", mbps))),
+            optional(self.status.map(|status| status.to_string())),
+            optional(self.error_kind.map(|kind| kind.as_str().to_string())),
+            optional(self.error.clone()),
+            optional(self.dns_ms.map(|ms| format!("{:.0}", ms))),
+            optional(self.connect_ms.map(|ms| format!("{:.0}", ms))),
+            optional(self.tls_ms.map(|ms| format!("{:.0}", ms))),
+            optional(self.body_ms.map(|ms| format!("{:.0}", ms))),
+            optional(self.rank.map(|rank| rank.to_string())),
+        ]
+        .join("\t")
+    }
+}
+
+pub fn print_json<T: Serialize + ?Sized>(value: &T) -> Result<()> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
+
+/// Print a list of records in a machine-readable format
+pub fn print_records(format: OutputFormat, records: &[RegistryRecord]) -> Result<()> {
+    match format {
+        OutputFormat::Json => print_json(records)?,
+        OutputFormat::Tsv => {
+            println!("{}", TSV_HEADER);
+            for record in records {
+                println!("{}", record.tsv_row());
+            }
+        }
+        OutputFormat::Text | OutputFormat::Toml | OutputFormat::Ini => {}
+    }
+    Ok(())
+}
+
+/// Print a single (possibly absent) record in a machine-readable format
+pub fn print_record(format: OutputFormat, record: Option<&RegistryRecord>) -> Result<()> {
+    match format {
+        OutputFormat::Json => print_json(&record)?,
+        OutputFormat::Tsv => {
+            print_records(format, record.map(std::slice::from_ref).unwrap_or(&[]))?
+        }
+        OutputFormat::Text | OutputFormat::Toml | OutputFormat::Ini => {}
+    }
+    Ok(())
+}
+
+/// Scoped registry emitted by `ls --scopes` in JSON/TSV mode
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ScopeRecord {
+    pub scope: String,
+    /// Stored registry name, `null` if the URL is not in the config
+    pub name: Option<String>,
+    pub url: String,
+    /// Set in the project `.npmrc` rather than the user one
+    pub local: bool,
+}
+
+const SCOPE_TSV_HEADER: &str = "scope\tname\turl\tlocal";
+
+/// Print `ls --scopes` entries in a machine-readable format
+pub fn print_scopes(format: OutputFormat, scopes: &[ScopeRecord]) -> Result<()> {
+    match format {
+        OutputFormat::Json => print_json(scopes)?,
+        OutputFormat::Tsv => {
+            println!("{}", SCOPE_TSV_HEADER);
+            for scope in scopes {
+                println!(
+                    "{}\t{}\t{}\t{}",
+                    scope.scope,
+                    scope.name.as_deref().unwrap_or_default(),
+                    scope.url,
+                    scope.local
+                );
+            }
+        }
+        OutputFormat::Text | OutputFormat::Toml | OutputFormat::Ini => {}
+    }
+    Ok(())
+}
+
+const TREND_TSV_HEADER: &str = "name\truns\tfailures\tfailure_rate\taverage_ms\tchange\tsparkline";
+
+/// Print `history` summaries in a machine-readable format
+pub fn print_trends(format: OutputFormat, trends: &[RegistryTrend]) -> Result<()> {
+    match format {
+        OutputFormat::Json => print_json(trends)?,
+        OutputFormat::Tsv => {
+            println!("{}", TREND_TSV_HEADER);
+            for trend in trends {
+                println!(
+                    "{}\t{}\t{}\t{:.2}\t{}\t{}\t{}",
+                    trend.name,
+                    trend.runs,
+                    trend.failures,
+                    trend.failure_rate(),
+                    trend
+                        .average_ms
+                        .map(|ms| format!("{:.0}", ms))
+                        .unwrap_or_default(),
+                    trend
+                        .change
+                        .map(|change| format!("{:.3}", change))
+                        .unwrap_or_default(),
+                    trend.sparkline
+                );
+            }
+        }
+        OutputFormat::Text | OutputFormat::Toml | OutputFormat::Ini => {}
+    }
+    Ok(())
+}
@@ -0,0 +1,135 @@
+use clap::ValueEnum;
+use serde::Serialize;
+use std::fmt;
+use std::path::Path;
+use tokio::fs;
+
+use super::resolve::find_executable;
+
+/// Package manager whose config `use`, `set-scope` and `set-auth` write to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PackageManager {
+    /// npm (and anything else reading `.npmrc`)
+    Npm,
+    /// Yarn 1 (`.yarnrc`)
+    Yarn,
+    /// Yarn 2+ (`.yarnrc.yml`)
+    YarnBerry,
+    /// pnpm (`.npmrc` locally, pnpm's `rc` file globally)
+    Pnpm,
+    /// Bun (`bunfig.toml`)
+    Bun,
+    /// Every package manager detected for the project
+    All,
+}
+
+impl fmt::Display for PackageManager {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PackageManager::Npm => write!(f, "npm"),
+            PackageManager::Yarn => write!(f, "yarn"),
+            PackageManager::YarnBerry => write!(f, "yarn-berry"),
+            PackageManager::Pnpm => write!(f, "pnpm"),
+            PackageManager::Bun => write!(f, "bun"),
+            PackageManager::All => write!(f, "all"),
+        }
+    }
+}
+
+impl PackageManager {
+    /// Guess the package manager of the project at `root`: the
+    /// `packageManager` field of `package.json` wins, then a package
+    /// manager specific config or lockfile, and npm otherwise.
+    /// Yarn 1 only comes from `packageManager`: a `.yarnrc` is also read
+    /// by tools that never run Yarn and does not identify the project.
+    pub async fn detect(root: &Path) -> Self {
+        if let Some(declared) = read_package_manager_field(root).await {
+            if let Some(pm) = Self::from_package_manager_field(&declared) {
+                return pm;
+            }
+        }
+        [
+            PackageManager::YarnBerry,
+            PackageManager::Pnpm,
+            PackageManager::Bun,
+        ]
+        .into_iter()
+        .find(|pm| pm.has_project_files(root))
+        .unwrap_or(PackageManager::Npm)
+    }
+
+    /// Every package manager used by the project at `root` or installed on
+    /// `PATH`. npm is always included since most tools fall back to `.npmrc`.
+    pub async fn detect_all(root: &Path) -> Vec<Self> {
+        let declared = match read_package_manager_field(root).await {
+            Some(declared) => Self::from_package_manager_field(&declared),
+            None => None,
+        };
+        let home = dirs::home_dir();
+        let has_home_file =
+            |name: &str| home.as_ref().is_some_and(|home| home.join(name).is_file());
+
+        let berry = declared == Some(PackageManager::YarnBerry)
+            || PackageManager::YarnBerry.has_project_files(root)
+            || has_home_file(".yarnrc.yml");
+        let classic = declared == Some(PackageManager::Yarn)
+            || PackageManager::Yarn.has_project_files(root)
+            || has_home_file(".yarnrc")
+            || (!berry && find_executable("yarn").is_some());
+
+        let mut detected = vec![PackageManager::Npm];
+        if classic {
+            detected.push(PackageManager::Yarn);
+        }
+        if berry {
+            detected.push(PackageManager::YarnBerry);
+        }
+        for (pm, binary) in [(PackageManager::Pnpm, "pnpm"), (PackageManager::Bun, "bun")] {
+            if declared == Some(pm)
+                || pm.has_project_files(root)
+                || find_executable(binary).is_some()
+            {
+                detected.push(pm);
+            }
+        }
+        detected
+    }
+
+    /// Config or lockfiles that only this package manager creates
+    fn has_project_files(&self, root: &Path) -> bool {
+        let files: &[&str] = match self {
+            PackageManager::YarnBerry => &[".yarnrc.yml"],
+            PackageManager::Yarn => &[".yarnrc"],
+            PackageManager::Pnpm => &["pnpm-workspace.yaml", "pnpm-lock.yaml"],
+            PackageManager::Bun => &["bunfig.toml", "bun.lock", "bun.lockb"],
+            PackageManager::Npm | PackageManager::All => &[],
+        };
+        files.iter().any(|file| root.join(file).is_file())
+    }
+
+    /// Parse a `packageManager` value such as `yarn@4.1.0+sha512.abc`
+    pub fn from_package_manager_field(value: &str) -> Option<Self> {
+        let (name, version) = value.split_once('@')?;
+        match name {
+            "npm" => Some(PackageManager::Npm),
+            "pnpm" => Some(PackageManager::Pnpm),
+            "bun" => Some(PackageManager::Bun),
+            "yarn" => {
+                let major: u32 = version.split('.').next()?.parse().ok()?;
+                Some(if major >= 2 {
+                    PackageManager::YarnBerry
+                } else {
+                    PackageManager::Yarn
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+async fn read_package_manager_field(root: &Path) -> Option<String> {
+    let content = fs::read_to_string(root.join("package.json")).await.ok()?;
+    let manifest: serde_json::Value = serde_json::from_str(&content).ok()?;
+    manifest.get("packageManager")?.as_str().map(str::to_string)
+}
@@ -0,0 +1,210 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::path::Path;
+use url::Url;
+
+use super::error::{Error, Result};
+use super::npmrc::{expand_env, npm_env_config, npmrc_path, Npmrc};
+use super::resolve::{find_project_root, global_npmrc_path};
+
+/// Proxies used for the requests rust-nrm sends to registries
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ProxyConfig {
+    /// Proxy for http URLs (npm's `proxy`)
+    pub http: Option<Url>,
+    /// Proxy for https URLs (npm's `https-proxy`), `http` when unset
+    pub https: Option<Url>,
+    /// Hosts reached directly (npm's `noproxy`): exact names, domain
+    /// suffixes such as `.corp.com`, `host:port`, or `*` for every host
+    pub no_proxy: Vec<String>,
+    /// Proxy of each registry, keyed by the registry URL and used ahead of
+    /// everything else for the URLs under it
+    pub registries: BTreeMap<String, Url>,
+}
+
+impl ProxyConfig {
+    /// `HTTP_PROXY`, `HTTPS_PROXY` and `NO_PROXY`, upper or lower case.
+    /// Like curl and reqwest, values that are not URLs are ignored.
+    pub fn from_env() -> Self {
+        let var = |name: &str| {
+            [name.to_uppercase(), name.to_lowercase()]
+                .into_iter()
+                .find_map(|name| env::var(name).ok().filter(|value| !value.is_empty()))
+        };
+        ProxyConfig {
+            http: var("http_proxy").and_then(|value| parse_proxy_url(&value).ok()),
+            https: var("https_proxy").and_then(|value| parse_proxy_url(&value).ok()),
+            no_proxy: var("no_proxy")
+                .map(|value| split_hosts(&value))
+                .unwrap_or_default(),
+            registries: BTreeMap::new(),
+        }
+    }
+
+    /// The `proxy`, `https-proxy` and `noproxy` keys of an `.npmrc`
+    pub fn from_npmrc(npmrc: &Npmrc) -> Result<Self> {
+        let proxy = |key: &str| npmrc.get_expanded(key).map(|value| parse_proxy_url(&value));
+        let mut no_proxy: Vec<String> = npmrc
+            .get_expanded("noproxy")
+            .map(|value| split_hosts(&value))
+            .unwrap_or_default();
+        for value in npmrc.get_all("noproxy") {
+            no_proxy.extend(split_hosts(&expand_env(value)));
+        }
+        Ok(ProxyConfig {
+            http: proxy("proxy").transpose()?,
+            https: proxy("https-proxy").transpose()?,
+            no_proxy,
+            registries: BTreeMap::new(),
+        })
+    }
+
+    /// The `npm_config_proxy`, `npm_config_https_proxy` and
+    /// `npm_config_noproxy` environment variables
+    pub fn from_npm_env() -> Result<Self> {
+        let proxy = |key: &str| npm_env_config(key).map(|value| parse_proxy_url(&value));
+        Ok(ProxyConfig {
+            http: proxy("proxy").transpose()?,
+            https: proxy("https-proxy").transpose()?,
+            no_proxy: npm_env_config("noproxy")
+                .map(|value| split_hosts(&value))
+                .unwrap_or_default(),
+            registries: BTreeMap::new(),
+        })
+    }
+
+    /// Fill every unset setting from `fallback`
+    pub fn or(self, fallback: ProxyConfig) -> Self {
+        let mut registries = fallback.registries;
+        registries.extend(self.registries);
+        ProxyConfig {
+            http: self.http.or(fallback.http),
+            https: self.https.or(fallback.https),
+            no_proxy: if self.no_proxy.is_empty() {
+                fallback.no_proxy
+            } else {
+                self.no_proxy
+            },
+            registries,
+        }
+    }
+
+    /// Send every request through `proxy` (`--proxy`), except for `no_proxy` hosts
+    pub fn with_override(self, proxy: Url) -> Self {
+        ProxyConfig {
+            http: Some(proxy.clone()),
+            https: Some(proxy),
+            no_proxy: self.no_proxy,
+            registries: BTreeMap::new(),
+        }
+    }
+
+    /// Reach `registry` and every URL under it through `proxy`
+    pub fn with_registry_proxy(mut self, registry: &str, proxy: Url) -> Result<Self> {
+        let mut prefix = Url::parse(registry)
+            .ok()
+            .filter(|url| url.host_str().is_some())
+            .ok_or_else(|| Error::Validation(format!("Invalid registry URL: {}", registry)))?;
+        if !prefix.path().ends_with('/') {
+            prefix.set_path(&format!("{}/", prefix.path()));
+        }
+        prefix.set_query(None);
+        prefix.set_fragment(None);
+        self.registries.insert(prefix.to_string(), proxy);
+        Ok(self)
+    }
+
+    /// Proxy to send a request for `url` through, `None` to connect directly
+    pub fn proxy_for(&self, url: &Url) -> Option<Url> {
+        // The most specific registry wins when several share a host
+        let registry_proxy = self
+            .registries
+            .iter()
+            .filter(|(prefix, _)| url.as_str().starts_with(prefix.as_str()))
+            .max_by_key(|(prefix, _)| prefix.len());
+        if let Some((_, proxy)) = registry_proxy {
+            return Some(proxy.clone());
+        }
+        if self.bypasses(url) {
+            return None;
+        }
+        match url.scheme() {
+            "https" => self.https.clone().or_else(|| self.http.clone()),
+            _ => self.http.clone(),
+        }
+    }
+
+    /// Whether `url` matches a `no_proxy` entry
+    fn bypasses(&self, url: &Url) -> bool {
+        let Some(host) = url
+            .host_str()
+            .map(|host| host.trim_matches(['[', ']']).to_lowercase())
+        else {
+            return false;
+        };
+        let port = url.port_or_known_default();
+
+        self.no_proxy.iter().any(|entry| {
+            if entry == "*" {
+                return true;
+            }
+            let (domain, entry_port) = match entry.rsplit_once(':') {
+                Some((domain, entry_port)) if entry_port.parse::<u16>().is_ok() => {
+                    (domain, entry_port.parse().ok())
+                }
+                _ => (entry.as_str(), None),
+            };
+            let domain = domain.trim_start_matches("*.").trim_start_matches('.');
+            let host_matches = host == domain || host.ends_with(&format!(".{}", domain));
+            host_matches && entry_port.is_none_or(|entry_port| port == Some(entry_port))
+        })
+    }
+}
+
+/// Proxy settings npm would use in `cwd`: `npm_config_*` variables, the
+/// project, user and global `.npmrc`, then `HTTP(S)_PROXY` and `NO_PROXY`
+pub async fn load_proxy_config(cwd: &Path) -> Result<ProxyConfig> {
+    let user_path = npmrc_path(false)?;
+    let project_path = find_project_root(cwd).join(".npmrc");
+
+    let mut config = ProxyConfig::from_npm_env()?;
+    // npm skips the project config when it is the user config
+    if project_path != user_path {
+        config = config.or(ProxyConfig::from_npmrc(&Npmrc::load(&project_path).await?)?);
+    }
+    config = config.or(ProxyConfig::from_npmrc(&Npmrc::load(&user_path).await?)?);
+    if let Some(global_path) = global_npmrc_path() {
+        config = config.or(ProxyConfig::from_npmrc(&Npmrc::load(&global_path).await?)?);
+    }
+    Ok(config.or(ProxyConfig::from_env()))
+}
+
+/// Parse an http or https proxy URL; like npm, `host:port` without a
+/// scheme means http
+pub fn parse_proxy_url(value: &str) -> Result<Url> {
+    let value = value.trim();
+    let with_scheme = if value.contains("://") {
+        value.to_string()
+    } else {
+        format!("http://{}", value)
+    };
+    let url = Url::parse(&with_scheme)
+        .ok()
+        .filter(|url| url.host_str().is_some())
+        .ok_or_else(|| Error::Validation(format!("Invalid proxy URL: {}", value)))?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(Error::Validation(format!(
+            "Invalid proxy URL {}: only http and https proxies are supported",
+            value
+        )));
+    }
+    Ok(url)
+}
+
+fn split_hosts(value: &str) -> Vec<String> {
+    value
+        .split([',', ' '])
+        .map(|host| host.trim().to_lowercase())
+        .filter(|host| !host.is_empty())
+        .collect()
+}
@@ -1,204 +1,336 @@
 use colored::*;
-use regex::Regex;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::env;
 use std::path::{Path, PathBuf};
 use tokio::fs;
 use toml;
+use url::Url;
 
+use super::auth::{get_auth, RegistryAuth};
+use super::error::{Error, Result};
+use super::npmrc::{npmrc_path, Npmrc};
+use super::output::{CurrentReport, RegistryRecord, ScopeRecord};
+use super::resolve::{effective_layer, registry_layers, RegistrySource};
 use super::speed_test::{SpeedTestResult, SpeedTester};
+use super::yarnrc::{yarnrc_path, Yarnrc};
 use super::Logger;
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
 pub struct Registry {
     pub registry: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub home: Option<String>,
+    /// Proxy for requests to this registry, ahead of the npmrc and
+    /// environment settings
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<String>,
+    /// Any other metadata stored with the registry, kept as-is
+    #[serde(flatten, default)]
+    pub extra: BTreeMap<String, toml::Value>,
 }
 
+/// `(name, registry, home)` of the registries shipped with rust-nrm
+pub const BUILTIN_REGISTRIES: &[(&str, &str, &str)] = &[
+    (
+        "npm",
+        "https://registry.npmjs.org/",
+        "https://www.npmjs.org",
+    ),
+    (
+        "yarn",
+        "https://registry.yarnpkg.com/",
+        "https://yarnpkg.com",
+    ),
+    (
+        "taobao",
+        "https://registry.npmmirror.com/",
+        "https://npmmirror.com/",
+    ),
+    (
+        "tencent",
+        "https://mirrors.cloud.tencent.com/npm/",
+        "https://mirrors.cloud.tencent.com/npm/",
+    ),
+    (
+        "npmMirror",
+        "https://skimdb.npmjs.com/registry/",
+        "https://skimdb.npmjs.com",
+    ),
+    (
+        "github",
+        "https://npm.pkg.github.com/",
+        "https://github.com",
+    ),
+];
+
 #[derive(Debug, Clone)]
 pub struct Store {
     pub registries: HashMap<String, Registry>,
+    /// File the registries are loaded from and saved to
+    pub config_path: PathBuf,
 }
 
 impl Store {
-    pub async fn load() -> Self {
-        let config_path = get_config_path();
+    /// Load from the default location (see `get_config_path`)
+    pub async fn load() -> Result<Self> {
+        Self::load_from(&get_config_path(None)?).await
+    }
+
+    pub async fn load_from(config_path: &Path) -> Result<Self> {
+        let config_path = config_path.to_path_buf();
         Logger::info(&format!("Config file path: {}", config_path.display()));
 
         if !config_path.exists() {
             Logger::info("Config file not found, creating default configuration...");
-            Self::create_default_config(&config_path).await;
+            Self::create_default_config(&config_path).await?;
         }
 
-        match fs::read_to_string(&config_path).await {
-            Ok(contents) => {
-                match toml::from_str(&contents) {
-                    Ok(registries) => Self { registries },
-                    Err(e) => {
-                        Logger::error(&format!("Failed to parse config file: {}", e));
-                        Self::create_default_store()
-                    }
-                }
-            }
-            Err(e) => {
-                Logger::error(&format!("Failed to read config file: {}", e));
-                Self::create_default_store()
-            }
-        }
+        let contents =
+            fs::read_to_string(&config_path)
+                .await
+                .map_err(|source| Error::ConfigIo {
+                    path: config_path.clone(),
+                    source,
+                })?;
+        let registries = toml::from_str(&contents).map_err(|e| Error::ConfigParse {
+            path: config_path.clone(),
+            message: e.to_string(),
+        })?;
+
+        Ok(Self {
+            registries,
+            config_path,
+        })
     }
 
-    async fn create_default_config(config_path: &Path) {
-        let default_registries = HashMap::from([
-            (
-                "npm".to_string(),
-                Registry {
-                    registry: "https://registry.npmjs.org/".to_string(),
-                    home: Some("https://www.npmjs.org".to_string()),
-                },
-            ),
-            (
-                "yarn".to_string(),
-                Registry {
-                    registry: "https://registry.yarnpkg.com/".to_string(),
-                    home: Some("https://yarnpkg.com".to_string()),
-                },
-            ),
-            (
-                "taobao".to_string(),
-                Registry {
-                    registry: "https://registry.npmmirror.com/".to_string(),
-                    home: Some("https://npmmirror.com/".to_string()),
-                },
-            ),
-            (
-                "tencent".to_string(),
-                Registry {
-                    registry: "https://mirrors.cloud.tencent.com/npm/".to_string(),
-                    home: Some("https://mirrors.cloud.tencent.com/npm/".to_string()),
-                },
-            ),
-            (
-                "npmMirror".to_string(),
-                Registry {
-                    registry: "https://skimdb.npmjs.com/registry/".to_string(),
-                    home: Some("https://skimdb.npmjs.com".to_string()),
-                },
-            ),
-            (
-                "github".to_string(),
-                Registry {
-                    registry: "https://npm.pkg.github.com/".to_string(),
-                    home: Some("https://github.com".to_string()),
-                },
-            ),
-        ]);
+    async fn create_default_config(config_path: &Path) -> Result<()> {
+        let io_error = |source| Error::ConfigIo {
+            path: config_path.to_path_buf(),
+            source,
+        };
 
         if let Some(parent) = config_path.parent() {
-            if !parent.exists() {
-                if let Err(e) = fs::create_dir_all(parent).await {
-                    Logger::error(&format!("Failed to create config directory: {}", e));
-                    return;
-                }
+            if !parent.as_os_str().is_empty() && !parent.exists() {
+                fs::create_dir_all(parent).await.map_err(io_error)?;
             }
         }
 
-        let toml = toml::to_string(&default_registries).unwrap();
-        if let Err(e) = fs::write(config_path, toml).await {
-            Logger::error(&format!("Failed to write default config: {}", e));
-        }
+        let toml =
+            toml::to_string(&Self::builtin_registries()).map_err(|e| Error::ConfigParse {
+                path: config_path.to_path_buf(),
+                message: e.to_string(),
+            })?;
+        fs::write(config_path, toml).await.map_err(io_error)
     }
 
-    fn create_default_store() -> Self {
-        Self {
-            registries: HashMap::from([
-                (
-                    "npm".to_string(),
-                    Registry {
-                        registry: "https://registry.npmjs.org/".to_string(),
-                        home: Some("https://www.npmjs.org".to_string()),
-                    },
-                ),
-                (
-                    "yarn".to_string(),
-                    Registry {
-                        registry: "https://registry.yarnpkg.com/".to_string(),
-                        home: Some("https://yarnpkg.com".to_string()),
-                    },
-                ),
-                (
-                    "taobao".to_string(),
-                    Registry {
-                        registry: "https://registry.npmmirror.com/".to_string(),
-                        home: Some("https://npmmirror.com/".to_string()),
-                    },
-                ),
-                (
-                    "tencent".to_string(),
-                    Registry {
-                        registry: "https://mirrors.cloud.tencent.com/npm/".to_string(),
-                        home: Some("https://mirrors.cloud.tencent.com/npm/".to_string()),
-                    },
-                ),
-                (
-                    "npmMirror".to_string(),
-                    Registry {
-                        registry: "https://skimdb.npmjs.com/registry/".to_string(),
-                        home: Some("https://skimdb.npmjs.com".to_string()),
-                    },
-                ),
+    /// Registries shipped with rust-nrm, as written to a fresh config
+    pub fn builtin_registries() -> HashMap<String, Registry> {
+        BUILTIN_REGISTRIES
+            .iter()
+            .map(|(name, registry, home)| {
                 (
-                    "github".to_string(),
+                    name.to_string(),
                     Registry {
-                        registry: "https://npm.pkg.github.com/".to_string(),
-                        home: Some("https://github.com".to_string()),
+                        registry: registry.to_string(),
+                        home: Some(home.to_string()),
+                        ..Default::default()
                     },
-                ),
-            ])
+                )
+            })
+            .collect()
+    }
+
+    /// Built-in registries are protected from accidental removal
+    pub fn is_builtin(name: &str) -> bool {
+        BUILTIN_REGISTRIES
+            .iter()
+            .any(|(builtin, _, _)| *builtin == name)
+    }
+
+    pub async fn save(&self) -> Result<()> {
+        let content = toml::to_string_pretty(&self.registries).map_err(|e| Error::ConfigParse {
+            path: self.config_path.clone(),
+            message: e.to_string(),
+        })?;
+        let io_error = |source| Error::ConfigIo {
+            path: self.config_path.clone(),
+            source,
+        };
+
+        if let Some(parent) = self.config_path.parent() {
+            if !parent.as_os_str().is_empty() && !parent.exists() {
+                fs::create_dir_all(parent).await.map_err(io_error)?;
+            }
         }
+        fs::write(&self.config_path, content)
+            .await
+            .map_err(io_error)
+    }
+
+    /// Look up a registry by name
+    pub fn get_registry(&self, name: &str) -> Result<&Registry> {
+        self.registries
+            .get(name)
+            .ok_or_else(|| Error::NotFound(format!("Registry {}", name)))
+    }
+
+    pub async fn get_current_registry(&self, is_local: bool) -> Result<Option<String>> {
+        Ok(self
+            .get_current_registry_url(is_local)
+            .await?
+            .and_then(|url| self.find_registry_name(&url).cloned()))
     }
 
-    pub async fn save(&self) {
-        let config_path = get_config_path();
-        let content = toml::to_string_pretty(&self.registries).unwrap();
-        if let Err(e) = fs::write(&config_path, content).await {
-            Logger::error(&format!("Failed to save config: {}", e));
+    /// Registry URL set in the global or local `.npmrc`, whether or not it is stored
+    pub async fn get_current_registry_url(&self, is_local: bool) -> Result<Option<String>> {
+        Ok(Npmrc::load(&npmrc_path(is_local)?)
+            .await?
+            .get_expanded("registry"))
+    }
+
+    /// Registry Yarn 1 uses, from the local `.yarnrc` or else `~/.yarnrc`
+    pub async fn get_current_yarn_registry(&self) -> Result<Option<String>> {
+        Ok(self
+            .get_current_yarn_registry_url()
+            .await?
+            .and_then(|url| self.find_registry_name(&url).cloned()))
+    }
+
+    /// Registry URL set in `.yarnrc`, whether or not it is stored
+    pub async fn get_current_yarn_registry_url(&self) -> Result<Option<String>> {
+        for is_local in [true, false] {
+            let yarnrc = Yarnrc::load(&yarnrc_path(is_local)?).await?;
+            if let Some(url) = yarnrc.get("registry") {
+                return Ok(Some(url.to_string()));
+            }
         }
+        Ok(None)
     }
 
-    pub async fn get_current_registry(&self, is_local: bool) -> Option<String> {
-        let npmrc_path = if is_local {
-            ".npmrc".to_string()
-        } else {
-            dirs::home_dir()
-                .map(|path| path.join(".npmrc").to_string_lossy().to_string())
-                .expect("Failed to get home directory")
-        };
+    /// Registry names in alphabetical order, for stable output
+    pub fn sorted_names(&self) -> Vec<&String> {
+        let mut names: Vec<&String> = self.registries.keys().collect();
+        names.sort_by_key(|name| name.to_lowercase());
+        names
+    }
 
-        if Path::new(&npmrc_path).exists() {
-            if let Ok(content) = fs::read_to_string(&npmrc_path).await {
-                let re = Regex::new(r"(?m)^\s*registry\s*=\s*(.+?)\s*$").unwrap();
-                if let Some(captures) = re.captures(&content) {
-                    let registry_url = captures.get(1).unwrap().as_str().to_string();
-                    // Find registry name by URL
-                    for (name, registry) in &self.registries {
-                        if registry.registry == registry_url {
-                            return Some(name.clone());
-                        }
-                    }
+    /// Machine-readable view of every stored registry
+    pub async fn registry_records(&self) -> Result<Vec<RegistryRecord>> {
+        let current_global = self.get_current_registry(false).await?;
+        let current_local = self.get_current_registry(true).await?;
+        let current_yarn = self.get_current_yarn_registry().await?;
+
+        Ok(self
+            .sorted_names()
+            .into_iter()
+            .map(|name| {
+                let registry = &self.registries[name];
+                RegistryRecord {
+                    name: Some(name.clone()),
+                    url: registry.registry.clone(),
+                    home: registry.home.clone(),
+                    active_global: current_global.as_ref() == Some(name),
+                    active_local: current_local.as_ref() == Some(name),
+                    active_yarn: current_yarn.as_ref() == Some(name),
+                    ..Default::default()
                 }
+            })
+            .collect())
+    }
+
+    /// The registry npm uses in `cwd`, the layer it comes from and every
+    /// layer npm consulted
+    pub async fn current_report(&self, cwd: &Path) -> Result<CurrentReport> {
+        let layers = registry_layers(cwd).await?;
+        let winner = effective_layer(&layers)
+            .cloned()
+            .ok_or_else(|| Error::NotFound("Registry configuration".to_string()))?;
+        let url = winner.url.unwrap_or_default();
+
+        let layer_url = |matches: fn(&RegistrySource) -> bool| {
+            layers
+                .iter()
+                .find(|layer| matches(&layer.source))
+                .and_then(|layer| layer.url.as_deref())
+        };
+        let user_url = layer_url(|source| matches!(source, RegistrySource::User(_)));
+        let project_url = layer_url(|source| matches!(source, RegistrySource::Project(_)));
+        let yarn_url = self.get_current_yarn_registry_url().await?;
+        let is_current =
+            |other: Option<&str>| other.is_some_and(|other| same_registry_url(other, &url));
+
+        let name = self.find_registry_name(&url).cloned();
+        let record = RegistryRecord {
+            home: name
+                .as_ref()
+                .and_then(|name| self.registries[name].home.clone()),
+            name,
+            active_global: is_current(user_url),
+            active_local: is_current(project_url),
+            active_yarn: is_current(yarn_url.as_deref()),
+            url,
+            ..Default::default()
+        };
+        Ok(CurrentReport {
+            record,
+            source: winner.source,
+            layers,
+        })
+    }
+
+    /// Machine-readable view of the scoped registries, global ones first
+    pub async fn scope_records(&self) -> Result<Vec<ScopeRecord>> {
+        let mut records = Vec::new();
+        for local in [false, true] {
+            for (scope, url) in self.get_scopes(local).await? {
+                records.push(ScopeRecord {
+                    scope,
+                    name: self.find_registry_name(&url).cloned(),
+                    url,
+                    local,
+                });
             }
         }
-        None
+        Ok(records)
     }
 
-    pub async fn list_registries(&self) {
+    /// Find registry name by URL
+    pub fn find_registry_name(&self, url: &str) -> Option<&String> {
+        self.registries
+            .iter()
+            .find(|(_, registry)| same_registry_url(&registry.registry, url))
+            .map(|(name, _)| name)
+    }
+
+    /// `(scope, url)` pairs configured in the global or local `.npmrc`
+    pub async fn get_scopes(&self, is_local: bool) -> Result<Vec<(String, String)>> {
+        Ok(Npmrc::load(&npmrc_path(is_local)?).await?.scopes())
+    }
+
+    /// Credentials for a registry URL, preferring the local `.npmrc`
+    pub async fn get_auth(&self, registry_url: &str) -> Result<Option<RegistryAuth>> {
+        for is_local in [true, false] {
+            let npmrc = Npmrc::load(&npmrc_path(is_local)?).await?;
+            if let Some(auth) = get_auth(&npmrc, registry_url) {
+                return Ok(Some(auth));
+            }
+        }
+        Ok(None)
+    }
+
+    pub async fn list_registries(&self) -> Result<()> {
         Logger::list("Available registries:");
 
         // Get current registries
-        let current_global = self.get_current_registry(false).await;
-        let current_local = self.get_current_registry(true).await;
+        let current_global = self.get_current_registry(false).await?;
+        let current_local = self.get_current_registry(true).await?;
+        let current_yarn = self.get_current_yarn_registry().await?;
+        let global_scopes = self.get_scopes(false).await?;
+        let local_scopes = self.get_scopes(true).await?;
 
-        for (name, registry) in self.registries.iter() {
+        for name in self.sorted_names() {
+            let registry = &self.registries[name];
             let mut tags = Vec::new();
 
             if let Some(current_global_name) = &current_global {
@@ -213,6 +345,26 @@ impl Store {
                 }
             }
 
+            if current_yarn.as_ref() == Some(name) {
+                tags.push("[YARN]".black().on_yellow());
+            }
+
+            if let Some(auth) = self.get_auth(&registry.registry).await? {
+                tags.push(format!("[AUTH {}]", auth.redacted()).white().on_magenta());
+            }
+
+            // Scopes routed to this registry
+            for (scope, url) in &global_scopes {
+                if same_registry_url(url, &registry.registry) {
+                    tags.push(scope.cyan());
+                }
+            }
+            for (scope, url) in &local_scopes {
+                if same_registry_url(url, &registry.registry) {
+                    tags.push(format!("{}(local)", scope).cyan());
+                }
+            }
+
             let tags_str = if !tags.is_empty() {
                 format!(
                     " {}",
@@ -232,39 +384,145 @@ impl Store {
                 tags_str
             );
         }
+        Ok(())
     }
 
-    pub fn set_current_use(&mut self, name: &str, is_local: bool) {
-        if self.registries.contains_key(name) {
-            Logger::info(&format!(
-                "Switched to registry: {} ({})",
-                name.green().bold(),
-                if is_local {
-                    "local".yellow()
+    pub async fn list_scopes(&self) -> Result<()> {
+        Logger::list("Scoped registries:");
+
+        let scopes = [
+            (false, self.get_scopes(false).await?),
+            (true, self.get_scopes(true).await?),
+        ];
+        if scopes.iter().all(|(_, entries)| entries.is_empty()) {
+            Logger::info("No scoped registries configured");
+            return Ok(());
+        }
+
+        for (is_local, entries) in scopes.iter() {
+            for (scope, url) in entries {
+                let name = self
+                    .find_registry_name(url)
+                    .map(|name| name.green().bold())
+                    .unwrap_or_else(|| "unknown".dimmed());
+                let tag = if *is_local {
+                    "[LOCAL]".white().on_green()
                 } else {
-                    "global".yellow()
-                }
-            ));
-        } else {
-            Logger::error(&format!("Registry not found: {}", name));
+                    "[GLOBAL]".white().on_blue()
+                };
+
+                println!(
+                    "{} -> {} ({}) {}",
+                    scope.cyan().bold(),
+                    name,
+                    url.yellow(),
+                    tag
+                );
+            }
         }
+        Ok(())
     }
 
-    pub async fn test_registry_speed(&self) -> Vec<SpeedTestResult> {
-        let tester = SpeedTester::new();
-        let registries: Vec<(String, String)> = self
-            .registries
+    pub fn set_current_use(&mut self, name: &str, is_local: bool) -> Result<()> {
+        self.get_registry(name)?;
+        Logger::info(&format!(
+            "Switched to registry: {} ({})",
+            name.green().bold(),
+            if is_local {
+                "local".yellow()
+            } else {
+                "global".yellow()
+            }
+        ));
+        Ok(())
+    }
+
+    /// Test every stored registry, ranked fastest first
+    pub async fn test_registry_speed(&self, tester: &SpeedTester) -> Result<Vec<SpeedTestResult>> {
+        let names: Vec<String> = self.sorted_names().into_iter().cloned().collect();
+        self.test_registries_speed(tester, &names).await
+    }
+
+    /// Test the named registries, ranked fastest first
+    pub async fn test_registries_speed(
+        &self,
+        tester: &SpeedTester,
+        names: &[String],
+    ) -> Result<Vec<SpeedTestResult>> {
+        let registries = names
             .iter()
-            .map(|(name, reg)| (name.clone(), reg.registry.clone()))
-            .collect();
+            .map(|name| Ok((name.clone(), self.get_registry(name)?.registry.clone())))
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(tester.test_all(&registries).await)
+    }
+}
+
+/// Validate a registry URL and normalize it to end with a slash
+pub fn normalize_registry_url(url: &str) -> Result<String> {
+    let invalid =
+        |reason: &str| Error::Validation(format!("Invalid registry URL {}: {}", url, reason));
+
+    let mut parsed = Url::parse(url.trim()).map_err(|e| invalid(&e.to_string()))?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err(invalid("only http and https are supported"));
+    }
+    if parsed.host_str().is_none_or(str::is_empty) {
+        return Err(invalid("missing host"));
+    }
+    if parsed.query().is_some() || parsed.fragment().is_some() {
+        return Err(invalid("query strings and fragments are not allowed"));
+    }
 
-        tester.test_all(&registries).await
+    if !parsed.path().ends_with('/') {
+        let path = format!("{}/", parsed.path());
+        parsed.set_path(&path);
     }
+    Ok(parsed.to_string())
 }
 
-fn get_config_path() -> PathBuf {
-    let home = dirs::home_dir().expect("Could not find home directory");
-    home.join(".config")
-        .join("rust-nrm")
-        .join("registries.toml")
+/// Validate a registry homepage, which must be an http or https URL
+pub fn validate_homepage_url(home: &str) -> Result<()> {
+    let valid = Url::parse(home)
+        .map(|url| matches!(url.scheme(), "http" | "https") && url.has_host())
+        .unwrap_or(false);
+    if !valid {
+        return Err(Error::Validation(format!(
+            "Invalid homepage URL {}: expected an http or https URL",
+            home
+        )));
+    }
+    Ok(())
+}
+
+/// Whether two registry URLs point at the same registry, e.g.
+/// `https://host/path` and `https://host/path/`
+pub fn same_registry_url(a: &str, b: &str) -> bool {
+    match (normalize_registry_url(a), normalize_registry_url(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a.trim_end_matches('/') == b.trim_end_matches('/'),
+    }
+}
+
+/// Resolve the config file location.
+///
+/// Precedence: explicit path (`--config`), `RUST_NRM_CONFIG`,
+/// `$XDG_CONFIG_HOME/rust-nrm/registries.toml`, then
+/// `~/.config/rust-nrm/registries.toml`.
+pub fn get_config_path(explicit: Option<&Path>) -> Result<PathBuf> {
+    if let Some(path) = explicit {
+        return Ok(path.to_path_buf());
+    }
+
+    if let Some(path) = env::var_os("RUST_NRM_CONFIG").filter(|path| !path.is_empty()) {
+        return Ok(PathBuf::from(path));
+    }
+
+    let config_dir = match env::var_os("XDG_CONFIG_HOME").map(PathBuf::from) {
+        Some(dir) if dir.is_absolute() => dir,
+        _ => dirs::home_dir()
+            .ok_or_else(|| Error::NotFound("Home directory".to_string()))?
+            .join(".config"),
+    };
+    Ok(config_dir.join("rust-nrm").join("registries.toml"))
 }
@@ -0,0 +1,155 @@
+use serde::Serialize;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use super::error::{Error, Result};
+use super::npmrc::{npm_env_config, npmrc_path, Npmrc};
+
+/// Registry npm falls back to when nothing is configured
+pub const DEFAULT_REGISTRY: &str = "https://registry.npmjs.org/";
+
+/// Where a registry setting can come from, in npm's order of precedence
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "layer", content = "path", rename_all = "lowercase")]
+pub enum RegistrySource {
+    /// `npm_config_registry` environment variable
+    Env,
+    /// `.npmrc` next to the nearest `package.json`/`node_modules`
+    Project(PathBuf),
+    /// User config (`~/.npmrc` or `npm_config_userconfig`)
+    User(PathBuf),
+    /// `$PREFIX/etc/npmrc`
+    Global(PathBuf),
+    /// npm's builtin default
+    Default,
+}
+
+impl fmt::Display for RegistrySource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RegistrySource::Env => write!(f, "env (npm_config_registry)"),
+            RegistrySource::Project(path) => write!(f, "project ({})", path.display()),
+            RegistrySource::User(path) => write!(f, "user ({})", path.display()),
+            RegistrySource::Global(path) => write!(f, "global ({})", path.display()),
+            RegistrySource::Default => write!(f, "default (builtin)"),
+        }
+    }
+}
+
+/// Registry value found (or not) in one config layer
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RegistryLayer {
+    #[serde(flatten)]
+    pub source: RegistrySource,
+    pub url: Option<String>,
+}
+
+/// Every layer npm consults for `registry`, highest precedence first.
+/// The last layer is always the builtin default, so at least one is set.
+pub async fn registry_layers(cwd: &Path) -> Result<Vec<RegistryLayer>> {
+    let mut layers = vec![RegistryLayer {
+        source: RegistrySource::Env,
+        url: npm_env_config("registry"),
+    }];
+
+    let user_path = npmrc_path(false)?;
+    let project_path = find_project_root(cwd).join(".npmrc");
+    // npm skips the project config when it is the user config
+    if project_path != user_path {
+        layers.push(RegistryLayer {
+            url: read_registry(&project_path).await?,
+            source: RegistrySource::Project(project_path),
+        });
+    }
+
+    layers.push(RegistryLayer {
+        url: read_registry(&user_path).await?,
+        source: RegistrySource::User(user_path),
+    });
+
+    if let Some(global_path) = global_npmrc_path() {
+        layers.push(RegistryLayer {
+            url: read_registry(&global_path).await?,
+            source: RegistrySource::Global(global_path),
+        });
+    }
+
+    layers.push(RegistryLayer {
+        source: RegistrySource::Default,
+        url: Some(DEFAULT_REGISTRY.to_string()),
+    });
+    Ok(layers)
+}
+
+/// The layer whose registry npm will actually use
+pub fn effective_layer(layers: &[RegistryLayer]) -> Option<&RegistryLayer> {
+    layers.iter().find(|layer| layer.url.is_some())
+}
+
+/// The working directory, failing with `Error::Io`
+pub fn current_dir() -> Result<PathBuf> {
+    std::env::current_dir().map_err(|source| Error::Io {
+        path: PathBuf::from("."),
+        source,
+    })
+}
+
+/// Project root of the working directory, where local configs live
+pub fn project_dir() -> Result<PathBuf> {
+    Ok(find_project_root(&current_dir()?))
+}
+
+/// npm's "local prefix": the nearest ancestor containing `package.json` or
+/// `node_modules`, or `cwd` itself when there is none
+pub fn find_project_root(cwd: &Path) -> PathBuf {
+    cwd.ancestors()
+        .find(|dir| dir.join("package.json").is_file() || dir.join("node_modules").is_dir())
+        .unwrap_or(cwd)
+        .to_path_buf()
+}
+
+/// `$PREFIX/etc/npmrc`, honouring `npm_config_globalconfig`, `npm_config_prefix`
+/// and `PREFIX` before deriving the prefix from the `node` binary on `PATH`
+pub fn global_npmrc_path() -> Option<PathBuf> {
+    if let Some(path) = npm_env_config("globalconfig") {
+        return Some(PathBuf::from(path));
+    }
+
+    let prefix = npm_env_config("prefix")
+        .map(PathBuf::from)
+        .or_else(|| {
+            std::env::var_os("PREFIX")
+                .filter(|p| !p.is_empty())
+                .map(PathBuf::from)
+        })
+        .or_else(node_prefix)?;
+    Some(prefix.join("etc").join("npmrc"))
+}
+
+fn node_prefix() -> Option<PathBuf> {
+    let node_path = find_executable("node")?;
+
+    // Unix installs live in `<prefix>/bin/node`, Windows in `<prefix>\node.exe`
+    let bin_dir = node_path.parent()?;
+    if cfg!(windows) {
+        Some(bin_dir.to_path_buf())
+    } else {
+        bin_dir.parent().map(Path::to_path_buf)
+    }
+}
+
+/// First `name` (`name.exe` on Windows) found on `PATH`
+pub fn find_executable(name: &str) -> Option<PathBuf> {
+    let file_name = if cfg!(windows) {
+        format!("{}.exe", name)
+    } else {
+        name.to_string()
+    };
+    std::env::split_paths(&std::env::var_os("PATH")?)
+        .map(|dir| dir.join(&file_name))
+        .find(|path| path.is_file())
+}
+
+async fn read_registry(path: &Path) -> Result<Option<String>> {
+    Ok(Npmrc::load(path).await?.get_expanded("registry"))
+}
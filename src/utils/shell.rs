@@ -0,0 +1,160 @@
+use clap::ValueEnum;
+use std::env;
+use std::fmt;
+use std::path::Path;
+
+/// Environment variables package managers read their default registry from
+pub const REGISTRY_ENV_VARS: &[&str] = &[
+    "npm_config_registry",
+    "YARN_NPM_REGISTRY_SERVER",
+    "BUN_CONFIG_REGISTRY",
+];
+
+/// Environment variable listing the variables the `shell-init` hook exported
+pub const HOOK_ENV_VAR: &str = "RUST_NRM_ENV";
+
+/// Shell syntax used by `env` and `shell-init`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+    Powershell,
+}
+
+impl fmt::Display for Shell {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Shell::Bash => write!(f, "bash"),
+            Shell::Zsh => write!(f, "zsh"),
+            Shell::Fish => write!(f, "fish"),
+            Shell::Powershell => write!(f, "powershell"),
+        }
+    }
+}
+
+impl Shell {
+    /// The login shell from `$SHELL`, PowerShell on Windows and bash otherwise
+    pub fn detect() -> Self {
+        let name = env::var_os("SHELL").and_then(|shell| {
+            Path::new(&shell)
+                .file_stem()
+                .map(|name| name.to_string_lossy().to_lowercase())
+        });
+        match name.as_deref() {
+            Some("zsh") => Shell::Zsh,
+            Some("fish") => Shell::Fish,
+            Some("pwsh") | Some("powershell") => Shell::Powershell,
+            Some(_) => Shell::Bash,
+            None if cfg!(windows) => Shell::Powershell,
+            None => Shell::Bash,
+        }
+    }
+
+    /// Commands pointing every package manager at `registry`
+    pub fn env_script(&self, registry: &str) -> String {
+        REGISTRY_ENV_VARS
+            .iter()
+            .map(|name| self.set_line(name, registry))
+            .collect()
+    }
+
+    /// Commands run by the `shell-init` hook. Inside a project pinning
+    /// `registry` the variables are exported and their names recorded in
+    /// `RUST_NRM_ENV`. Elsewhere only the variables listed in `exported`,
+    /// the current `RUST_NRM_ENV`, are unset, so values the user exported
+    /// are left alone and nothing is printed when there is nothing to undo.
+    pub fn auto_env_script(&self, registry: Option<&str>, exported: Option<&str>) -> String {
+        if let Some(url) = registry {
+            let mut script = self.env_script(url);
+            script.push_str(&self.set_line(HOOK_ENV_VAR, &REGISTRY_ENV_VARS.join(",")));
+            return script;
+        }
+        let Some(exported) = exported.filter(|exported| !exported.is_empty()) else {
+            return String::new();
+        };
+        exported
+            .split(',')
+            .filter(|name| REGISTRY_ENV_VARS.contains(name))
+            .chain([HOOK_ENV_VAR])
+            .map(|name| self.unset_line(name))
+            .collect()
+    }
+
+    fn set_line(&self, name: &str, value: &str) -> String {
+        match self {
+            Shell::Bash | Shell::Zsh => format!("export {}={}\n", name, posix_quote(value)),
+            Shell::Fish => format!("set -gx {} {}\n", name, posix_quote(value)),
+            Shell::Powershell => format!("$env:{} = {}\n", name, powershell_quote(value)),
+        }
+    }
+
+    fn unset_line(&self, name: &str) -> String {
+        match self {
+            Shell::Bash | Shell::Zsh => format!("unset {}\n", name),
+            Shell::Fish => format!("set -e {}\n", name),
+            Shell::Powershell => {
+                format!("Remove-Item Env:{} -ErrorAction SilentlyContinue\n", name)
+            }
+        }
+    }
+
+    /// Hook running `rust-nrm env --auto` whenever the directory changes
+    pub fn init_script(&self) -> String {
+        let script = match self {
+            Shell::Bash => BASH_HOOK,
+            Shell::Zsh => ZSH_HOOK,
+            Shell::Fish => FISH_HOOK,
+            Shell::Powershell => POWERSHELL_HOOK,
+        };
+        script.to_string()
+    }
+}
+
+/// Single-quote for POSIX shells and fish
+fn posix_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+fn powershell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+const BASH_HOOK: &str = r#"_rust_nrm_hook() {
+  local previous_exit_status=$?
+  if [[ "$PWD" != "${_RUST_NRM_LAST_DIR:-}" ]]; then
+    _RUST_NRM_LAST_DIR="$PWD"
+    eval "$(rust-nrm env --auto --shell bash)"
+  fi
+  return $previous_exit_status
+}
+if [[ ";${PROMPT_COMMAND:-};" != *";_rust_nrm_hook;"* ]]; then
+  PROMPT_COMMAND="_rust_nrm_hook${PROMPT_COMMAND:+;$PROMPT_COMMAND}"
+fi
+"#;
+
+const ZSH_HOOK: &str = r#"_rust_nrm_hook() {
+  eval "$(rust-nrm env --auto --shell zsh)"
+}
+autoload -Uz add-zsh-hook
+add-zsh-hook chpwd _rust_nrm_hook
+_rust_nrm_hook
+"#;
+
+const FISH_HOOK: &str = r#"function _rust_nrm_hook --on-variable PWD
+    rust-nrm env --auto --shell fish | source
+end
+_rust_nrm_hook
+"#;
+
+const POWERSHELL_HOOK: &str = r#"$global:_RustNrmLastDir = $null
+$global:_RustNrmPrompt = $function:prompt
+function global:prompt {
+    if ($PWD.Path -ne $global:_RustNrmLastDir) {
+        $global:_RustNrmLastDir = $PWD.Path
+        $script = (& rust-nrm env --auto --shell powershell) -join "`n"
+        if ($script) { Invoke-Expression $script }
+    }
+    & $global:_RustNrmPrompt
+}
+"#;
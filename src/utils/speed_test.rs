@@ -1,120 +1,668 @@
+use super::error::{Error, Result};
+use super::proxy::ProxyConfig;
+use super::timing::{measure_phases_via, PhaseTimings};
 use super::Logger;
 use colored::Colorize;
 use reqwest;
-use std::sync::atomic::{AtomicUsize, Ordering};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::cmp::Ordering;
+use std::fmt;
 use std::sync::Arc;
-use std::time::Instant;
-use tokio::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, Semaphore};
 use tokio::task;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default, PartialEq)]
 pub struct SpeedTestResult {
     pub name: String,
     pub url: String,
+    /// Median response time in seconds, used for ranking
     pub response_time: f64,
     pub is_success: bool,
+    /// Latency of the measured samples, in seconds
+    pub stats: LatencyStats,
+    /// Share of the measured samples that got a response, from 0.0 to 1.0
+    pub success_ratio: f64,
+    /// Package download measurements, when testing with a package
+    pub download: Option<DownloadStats>,
+    /// HTTP status of the last response, if any arrived
+    pub status: Option<u16>,
+    /// Why the last failed sample failed
+    pub failure: Option<TestFailure>,
+    /// Median time of each request phase, with `test --detailed`
+    pub phases: Option<PhaseTimings>,
+}
+
+/// Stage at which a speed test request failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FailureKind {
+    /// The host name could not be resolved
+    Dns,
+    /// No TCP connection could be opened
+    Connect,
+    /// The TLS handshake or certificate check failed
+    Tls,
+    /// The request did not finish in time
+    Timeout,
+    /// The registry answered with an error status or an unusable body
+    Http,
+}
+
+impl FailureKind {
+    /// Name used in JSON and TSV output
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FailureKind::Dns => "dns",
+            FailureKind::Connect => "connect",
+            FailureKind::Tls => "tls",
+            FailureKind::Timeout => "timeout",
+            FailureKind::Http => "http",
+        }
+    }
+}
+
+impl fmt::Display for FailureKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FailureKind::Dns => write!(f, "DNS"),
+            FailureKind::Connect => write!(f, "connect"),
+            FailureKind::Tls => write!(f, "TLS"),
+            FailureKind::Timeout => write!(f, "timeout"),
+            FailureKind::Http => write!(f, "HTTP"),
+        }
+    }
+}
+
+/// A failed speed test request
+#[derive(Debug, Clone, PartialEq)]
+pub struct TestFailure {
+    pub kind: FailureKind,
+    /// Status of the error response, for `FailureKind::Http`
+    pub status: Option<u16>,
+    pub message: String,
+}
+
+impl TestFailure {
+    /// Whether trying again could succeed; a 4xx answer will not change
+    pub fn is_retryable(&self) -> bool {
+        !matches!(self.status, Some(status) if (400..500).contains(&status))
+    }
+}
+
+impl fmt::Display for TestFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl From<reqwest::Error> for TestFailure {
+    fn from(error: reqwest::Error) -> Self {
+        let status = error.status();
+        let kind = failure_kind(&error);
+        let message = match status {
+            Some(status) => format!("HTTP {}", status),
+            None => format!("{}: {}", kind, root_cause(&error)),
+        };
+        TestFailure {
+            kind,
+            status: status.map(|status| status.as_u16()),
+            message,
+        }
+    }
+}
+
+/// reqwest only flags connect errors, so DNS and TLS failures are told
+/// apart by the errors they wrap
+fn failure_kind(error: &reqwest::Error) -> FailureKind {
+    if error.is_timeout() {
+        return FailureKind::Timeout;
+    }
+    if !error.is_connect() {
+        return FailureKind::Http;
+    }
+    let chain = error_chain(error).to_lowercase();
+    if ["dns", "lookup", "resolve"]
+        .iter()
+        .any(|word| chain.contains(word))
+    {
+        FailureKind::Dns
+    } else if ["tls", "ssl", "certificate", "handshake"]
+        .iter()
+        .any(|word| chain.contains(word))
+    {
+        FailureKind::Tls
+    } else {
+        FailureKind::Connect
+    }
+}
+
+fn error_chain(error: &(dyn std::error::Error + 'static)) -> String {
+    let mut chain = error.to_string();
+    let mut source = error.source();
+    while let Some(error) = source {
+        chain.push_str(": ");
+        chain.push_str(&error.to_string());
+        source = error.source();
+    }
+    chain
+}
+
+/// The innermost error, which names the actual problem
+fn root_cause(error: &(dyn std::error::Error + 'static)) -> String {
+    let mut error = error;
+    while let Some(source) = error.source() {
+        error = source;
+    }
+    error.to_string()
+}
+
+/// A successful measured request
+struct Fetched {
+    status: u16,
+    download: Option<DownloadSample>,
+    phases: Option<PhaseTimings>,
+}
+
+/// Package used by `test --package` when no name is given
+pub const DEFAULT_TEST_PACKAGE: &str = "lodash";
+
+/// Accept header npm uses to get the abbreviated packument
+const ABBREVIATED_PACKUMENT: &str =
+    "application/vnd.npm.install-v1+json; q=1.0, application/json; q=0.8, */*";
+
+/// `name[@version]` of the package fetched by `test --package`. The version
+/// may also be a dist-tag and defaults to `latest`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PackageSpec {
+    pub name: String,
+    pub version: Option<String>,
+}
+
+impl PackageSpec {
+    pub fn parse(spec: &str) -> Result<Self> {
+        let spec = spec.trim();
+        // The `@` of a scope is part of the name
+        let (name, version) = match spec.rfind('@') {
+            Some(index) if index > 0 => (&spec[..index], Some(&spec[index + 1..])),
+            _ => (spec, None),
+        };
+        let valid_name = !name.is_empty()
+            && (!name.starts_with('@') || name.split('/').filter(|p| !p.is_empty()).count() == 2);
+        if !valid_name || version == Some("") {
+            return Err(Error::Validation(format!(
+                "Invalid package {}: expected name[@version]",
+                spec
+            )));
+        }
+        Ok(PackageSpec {
+            name: name.to_string(),
+            version: version.map(str::to_string),
+        })
+    }
+
+    /// Packument URL under `registry`; the scope separator is escaped like npm does
+    pub fn packument_url(&self, registry: &str) -> String {
+        format!(
+            "{}/{}",
+            registry.trim_end_matches('/'),
+            self.name.replace('/', "%2f")
+        )
+    }
+
+    /// Tarball URL of the requested version in a packument
+    pub fn tarball_url(&self, packument: &Value) -> Option<String> {
+        let wanted = self.version.as_deref().unwrap_or("latest");
+        let versions = packument.get("versions")?;
+        let version = if versions.get(wanted).is_some() {
+            wanted
+        } else {
+            packument.get("dist-tags")?.get(wanted)?.as_str()?
+        };
+        versions
+            .get(version)?
+            .get("dist")?
+            .get("tarball")?
+            .as_str()
+            .map(str::to_string)
+    }
+}
+
+impl fmt::Display for PackageSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.version {
+            Some(version) => write!(f, "{}@{}", self.name, version),
+            None => write!(f, "{}", self.name),
+        }
+    }
+}
+
+/// One fetch of a packument and its tarball, times in seconds
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct DownloadSample {
+    /// Until the packument response headers arrived
+    ttfb: f64,
+    /// Packument and tarball, start to end
+    total: f64,
+    /// Tarball transfer alone
+    tarball_time: f64,
+    tarball_bytes: u64,
+}
+
+/// Package download measurements of a registry, times in seconds
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct DownloadStats {
+    /// Median time to the first byte of the packument
+    pub ttfb: f64,
+    /// Median time to fetch the packument and the tarball
+    pub total: f64,
+    /// Size of the tarball
+    pub tarball_bytes: u64,
+    /// Tarball throughput over all samples, in MB/s
+    pub throughput: f64,
+}
+
+impl DownloadStats {
+    fn from_samples(samples: &[DownloadSample]) -> Option<Self> {
+        if samples.is_empty() {
+            return None;
+        }
+        let median = |value: fn(&DownloadSample) -> f64| {
+            LatencyStats::from_samples(&samples.iter().map(value).collect::<Vec<_>>()).median
+        };
+        let bytes: u64 = samples.iter().map(|s| s.tarball_bytes).sum();
+        let time: f64 = samples.iter().map(|s| s.tarball_time).sum();
+        Some(DownloadStats {
+            ttfb: median(|s| s.ttfb),
+            total: median(|s| s.total),
+            tarball_bytes: samples[0].tarball_bytes,
+            throughput: if time > 0.0 {
+                bytes as f64 / 1_000_000.0 / time
+            } else {
+                0.0
+            },
+        })
+    }
+}
+
+/// Summary of the response times of several requests, in seconds
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct LatencyStats {
+    pub min: f64,
+    pub median: f64,
+    pub p95: f64,
+    pub max: f64,
+    pub stddev: f64,
+}
+
+impl LatencyStats {
+    /// Statistics of `samples`, all zero when there are none
+    pub fn from_samples(samples: &[f64]) -> Self {
+        if samples.is_empty() {
+            return Self::default();
+        }
+        let mut sorted = samples.to_vec();
+        sorted.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+
+        let count = sorted.len() as f64;
+        let mean = sorted.iter().sum::<f64>() / count;
+        let variance = sorted.iter().map(|s| (s - mean).powi(2)).sum::<f64>() / count;
+
+        LatencyStats {
+            min: sorted[0],
+            median: percentile(&sorted, 0.5),
+            p95: percentile(&sorted, 0.95),
+            max: sorted[sorted.len() - 1],
+            stddev: variance.sqrt(),
+        }
+    }
+}
+
+/// Linear interpolation between the closest ranks of sorted samples
+fn percentile(sorted: &[f64], quantile: f64) -> f64 {
+    let position = (sorted.len() - 1) as f64 * quantile;
+    let lower = position.floor() as usize;
+    let upper = position.ceil() as usize;
+    sorted[lower] + (sorted[upper] - sorted[lower]) * (position - lower as f64)
+}
+
+/// Successful registries first, those answering more of the samples ahead
+/// of faster ones that failed some, then by median latency; failures last
+/// by name. The median only covers the samples that got a response, so a
+/// registry answering 1 request in 5 must not win on it.
+pub fn rank_results(results: &mut [SpeedTestResult]) {
+    results.sort_by(|a, b| {
+        b.is_success
+            .cmp(&a.is_success)
+            .then_with(|| {
+                if a.is_success {
+                    b.success_ratio
+                        .partial_cmp(&a.success_ratio)
+                        .unwrap_or(Ordering::Equal)
+                        .then_with(|| {
+                            a.response_time
+                                .partial_cmp(&b.response_time)
+                                .unwrap_or(Ordering::Equal)
+                        })
+                } else {
+                    Ordering::Equal
+                }
+            })
+            .then_with(|| a.name.cmp(&b.name))
+    });
+}
+
+/// Client settings for embedding the speed test in other tools
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpeedTesterConfig {
+    /// Limit for a single request; slower ones fail with
+    /// `FailureKind::Timeout`
+    pub timeout: Duration,
+    /// Registries tested at the same time (at least one)
+    pub concurrency: usize,
+    /// Extra attempts for a failed request. HTTP 4xx answers are final.
+    pub retries: u32,
+    /// Wait before the first retry, doubled for each following one
+    pub backoff: Duration,
+    /// Proxies to reach the registries through, none by default;
+    /// `load_proxy_config` gives the ones npm would use
+    pub proxy: ProxyConfig,
+}
+
+impl Default for SpeedTesterConfig {
+    fn default() -> Self {
+        SpeedTesterConfig {
+            timeout: Duration::from_secs(5),
+            concurrency: 8,
+            retries: 0,
+            backoff: Duration::from_millis(200),
+            proxy: ProxyConfig::default(),
+        }
+    }
 }
 
 #[derive(Clone)]
 pub struct SpeedTester {
     client: reqwest::Client,
+    samples: usize,
+    warmup: usize,
+    package: Option<PackageSpec>,
+    detailed: bool,
+    config: SpeedTesterConfig,
 }
 
 impl SpeedTester {
-    pub fn new() -> Self {
-        SpeedTester {
+    pub fn new() -> Result<Self> {
+        Self::with_config(SpeedTesterConfig::default())
+    }
+
+    pub fn with_config(config: SpeedTesterConfig) -> Result<Self> {
+        let proxy = config.proxy.clone();
+        Ok(SpeedTester {
             client: reqwest::Client::builder()
-                .timeout(std::time::Duration::from_secs(5)) // 5 seconds timeout
-                .build()
-                .unwrap_or_else(|_| reqwest::Client::new()),
-        }
+                .timeout(config.timeout)
+                .proxy(reqwest::Proxy::custom(move |url| proxy.proxy_for(url)))
+                .build()?,
+            samples: 1,
+            warmup: 0,
+            package: None,
+            detailed: false,
+            config,
+        })
+    }
+
+    /// Number of measured requests per registry (at least one)
+    pub fn with_samples(mut self, samples: usize) -> Self {
+        self.samples = samples.max(1);
+        self
+    }
+
+    /// Requests sent before measuring, so connection setup does not skew
+    /// the samples
+    pub fn with_warmup(mut self, warmup: usize) -> Self {
+        self.warmup = warmup;
+        self
+    }
+
+    /// Measure fetching `package` (packument and tarball) instead of the
+    /// registry root, which some mirrors answer with an error page
+    pub fn with_package(mut self, package: PackageSpec) -> Self {
+        self.package = Some(package);
+        self
+    }
+
+    /// Time DNS, TCP connect, TLS, time-to-first-byte and body separately.
+    /// Each sample opens its own connection, so `package` is not used.
+    pub fn with_detailed(mut self, detailed: bool) -> Self {
+        self.detailed = detailed;
+        self
     }
 
     pub async fn test_registry(&self, name: &str, url: &str) -> SpeedTestResult {
-        let start = Instant::now();
-        let result = self.client.get(url).send().await;
-        let elapsed = start.elapsed().as_secs_f64();
+        for _ in 0..self.warmup {
+            let _ = self.fetch(url).await;
+        }
+
+        let mut succeeded = Vec::new();
+        let mut failed = Vec::new();
+        let mut downloads = Vec::new();
+        let mut phases = Vec::new();
+        let mut status = None;
+        let mut failure = None;
+        for _ in 0..self.samples {
+            let (elapsed, result) = self.fetch_with_retries(url).await;
+            match result {
+                Ok(fetched) => {
+                    succeeded.push(elapsed);
+                    status = Some(fetched.status);
+                    downloads.extend(fetched.download);
+                    phases.extend(fetched.phases);
+                }
+                Err(error) => {
+                    failed.push(elapsed);
+                    status = error.status.or(status);
+                    failure = Some(error);
+                }
+            }
+        }
+
+        let success_ratio = succeeded.len() as f64 / self.samples as f64;
+        let stats = if succeeded.is_empty() {
+            LatencyStats::from_samples(&failed)
+        } else {
+            LatencyStats::from_samples(&succeeded)
+        };
 
         SpeedTestResult {
             name: name.to_string(),
             url: url.to_string(),
-            response_time: elapsed,
-            is_success: result.is_ok(),
+            response_time: stats.median,
+            is_success: !succeeded.is_empty(),
+            stats,
+            success_ratio,
+            download: DownloadStats::from_samples(&downloads),
+            status,
+            failure,
+            phases: PhaseTimings::median(&phases),
+        }
+    }
+
+    /// Time `fetch`, retrying with backoff while it fails. Only the last
+    /// attempt counts towards the sample.
+    async fn fetch_with_retries(
+        &self,
+        url: &str,
+    ) -> (f64, std::result::Result<Fetched, TestFailure>) {
+        let mut attempt = 0;
+        loop {
+            let start = Instant::now();
+            let result = self.fetch(url).await;
+            let elapsed = start.elapsed().as_secs_f64();
+            match result {
+                Err(error) if attempt < self.config.retries && error.is_retryable() => {
+                    tokio::time::sleep(self.config.backoff * 2u32.saturating_pow(attempt)).await;
+                    attempt += 1;
+                }
+                Err(mut error) if error.kind == FailureKind::Timeout => {
+                    error.message = format!(
+                        "{}: no response within {}ms",
+                        error.kind,
+                        self.config.timeout.as_millis()
+                    );
+                    return (elapsed, Err(error));
+                }
+                result => return (elapsed, result),
+            }
         }
     }
 
+    /// One measured request: the registry root, or the package download
+    async fn fetch(&self, url: &str) -> std::result::Result<Fetched, TestFailure> {
+        if self.detailed {
+            let (status, phases) =
+                measure_phases_via(url, self.config.timeout, &self.config.proxy).await?;
+            return Ok(Fetched {
+                status,
+                download: None,
+                phases: Some(phases),
+            });
+        }
+        match &self.package {
+            Some(package) => {
+                let (status, download) = self.fetch_package(url, package).await?;
+                Ok(Fetched {
+                    status,
+                    download: Some(download),
+                    phases: None,
+                })
+            }
+            None => {
+                let response = self.client.get(url).send().await?.error_for_status()?;
+                Ok(Fetched {
+                    status: response.status().as_u16(),
+                    download: None,
+                    phases: None,
+                })
+            }
+        }
+    }
+
+    /// Fetch the abbreviated packument, then download the tarball it points to
+    async fn fetch_package(
+        &self,
+        url: &str,
+        package: &PackageSpec,
+    ) -> std::result::Result<(u16, DownloadSample), TestFailure> {
+        let start = Instant::now();
+        let response = self
+            .client
+            .get(package.packument_url(url))
+            .header(reqwest::header::ACCEPT, ABBREVIATED_PACKUMENT)
+            .send()
+            .await?
+            .error_for_status()?;
+        let ttfb = start.elapsed().as_secs_f64();
+        let status = response.status().as_u16();
+        let packument: Value = response.json().await?;
+        let tarball = package.tarball_url(&packument).ok_or_else(|| TestFailure {
+            kind: FailureKind::Http,
+            status: Some(status),
+            message: format!("{} is not in the packument", package),
+        })?;
+
+        let tarball_start = Instant::now();
+        let mut response = self.client.get(tarball).send().await?.error_for_status()?;
+        let mut tarball_bytes = 0;
+        while let Some(chunk) = response.chunk().await? {
+            tarball_bytes += chunk.len() as u64;
+        }
+
+        Ok((
+            status,
+            DownloadSample {
+                ttfb,
+                total: start.elapsed().as_secs_f64(),
+                tarball_time: tarball_start.elapsed().as_secs_f64(),
+                tarball_bytes,
+            },
+        ))
+    }
+
     fn format_time(seconds: f64) -> String {
         format!("{:.0}ms", seconds * 1000.0)
     }
 
     pub async fn test_all(&self, registries: &[(String, String)]) -> Vec<SpeedTestResult> {
-        Logger::info("Testing registry speeds...");
-        println!(); // Add a blank line for better readability
-
-        // Create a shared vector for results
-        let results = Arc::new(Mutex::new(Vec::new()));
+        let target = match &self.package {
+            _ if self.detailed => " phase by phase".to_string(),
+            Some(package) => format!(" by downloading {}", package.to_string().bold()),
+            None => String::new(),
+        };
+        Logger::info(&format!(
+            "Testing registry speeds{} ({} sample{} per registry)...",
+            target,
+            self.samples,
+            if self.samples == 1 { "" } else { "s" }
+        ));
+        Logger::line(""); // Add a blank line for better readability
 
-        // Create an atomic counter for ranking
-        let speed_rank = Arc::new(AtomicUsize::new(1));
-
-        // Create a vector of tasks for parallel execution
-        let mut handles: Vec<task::JoinHandle<()>> = Vec::new();
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let permits = Arc::new(Semaphore::new(self.config.concurrency.max(1)));
         for (name, url) in registries.iter() {
             let name = name.clone();
             let url = url.clone();
             let client = self.clone();
-            let results = Arc::clone(&results);
-            let speed_rank = Arc::clone(&speed_rank);
-
-            let handle = task::spawn(async move {
-                let test_result = client.test_registry(&name, &url).await;
-
-                let status = if test_result.is_success {
-                    "✓".green()
-                } else {
-                    "✗".red()
-                };
-
-                let time_str = Self::format_time(test_result.response_time);
-                let time_display = time_str.normal();
-                let current_rank = speed_rank.fetch_add(1, Ordering::Relaxed);
-                let rank = format!("#{}", current_rank).bold();
+            let sender = sender.clone();
+            let permits = Arc::clone(&permits);
 
-                println!(
-                    "{} {} {} {} {}",
-                    rank,
-                    status,
-                    test_result.name.bold(),
-                    "->".dimmed(),
-                    time_display
-                );
-
-                // Store the result
-                let mut results = results.lock().await;
-                results.push(test_result);
+            task::spawn(async move {
+                let _permit = permits.acquire().await;
+                let _ = sender.send(client.test_registry(&name, &url).await);
             });
-            handles.push(handle);
         }
+        drop(sender);
 
-        // Wait for all tasks to complete
-        for handle in handles {
-            let _ = handle.await;
+        // Redraw the ranking in place on a terminal; elsewhere completion
+        // order is noise, so only the final table is printed
+        let live = Logger::is_terminal();
+        let mut table = LiveTable::new(registries.iter().map(|(name, _)| name.clone()));
+        let mut drawn = 0;
+        let mut ticker = tokio::time::interval(SPINNER_INTERVAL);
+        loop {
+            tokio::select! {
+                result = receiver.recv() => match result {
+                    Some(result) => table.finish(result),
+                    None => break,
+                },
+                _ = ticker.tick(), if live => table.tick(),
+            }
+            if live {
+                let rows = table.rows();
+                Logger::redraw(drawn, &rows);
+                drawn = rows.len();
+            }
+        }
+        if live {
+            Logger::redraw(drawn, &[]);
         }
 
-        // Get all results
-        let final_results = results.lock().await.clone();
+        let mut final_results = table.done;
+        rank_results(&mut final_results);
 
-        // Find the fastest successful registry
-        let fastest = final_results
-            .iter()
-            .filter(|r| r.is_success)
-            .min_by(|a, b| a.response_time.partial_cmp(&b.response_time).unwrap());
+        if self.detailed {
+            Self::print_phase_table(&final_results);
+        } else if self.package.is_some() {
+            Self::print_download_table(&final_results);
+        } else {
+            Self::print_table(&final_results);
+        }
 
         // Show the fastest registry
-        if let Some(fastest) = fastest {
-            println!(); // Add a blank line
+        if let Some(fastest) = final_results.first().filter(|r| r.is_success) {
+            Logger::line(""); // Add a blank line
             Logger::success(&format!(
-                "Fastest registry is {} ({})",
+                "Fastest registry is {} ({} median)",
                 fastest.name.bold(),
                 Self::format_time(fastest.response_time)
             ));
@@ -122,4 +670,205 @@ impl SpeedTester {
 
         final_results
     }
+
+    /// Ranked results with their latency statistics
+    fn print_table(results: &[SpeedTestResult]) {
+        let name_width = results
+            .iter()
+            .map(|r| r.name.len())
+            .chain(["Registry".len()])
+            .max()
+            .unwrap_or_default();
+
+        Logger::line(
+            &format!(
+                "{:>4}  {:<name_width$}  {:>8}  {:>8}  {:>8}  {:>8}  {:>8}  {:>7}",
+                "Rank", "Registry", "Median", "Min", "P95", "Max", "Stddev", "Success"
+            )
+            .bold()
+            .to_string(),
+        );
+        for (rank, result) in results.iter().enumerate() {
+            if !result.is_success {
+                Self::print_failure_row(result, name_width);
+                continue;
+            }
+            let stats = &result.stats;
+            Logger::line(&format!(
+                "{:>4}  {:<name_width$}  {:>8}  {:>8}  {:>8}  {:>8}  {:>8}  {:>6.0}%",
+                format!("#{}", rank + 1),
+                result.name,
+                Self::format_time(stats.median),
+                Self::format_time(stats.min),
+                Self::format_time(stats.p95),
+                Self::format_time(stats.max),
+                Self::format_time(stats.stddev),
+                result.success_ratio * 100.0
+            ));
+        }
+    }
+
+    /// Ranked results with their package download measurements
+    fn print_download_table(results: &[SpeedTestResult]) {
+        let name_width = results
+            .iter()
+            .map(|r| r.name.len())
+            .chain(["Registry".len()])
+            .max()
+            .unwrap_or_default();
+
+        Logger::line(
+            &format!(
+                "{:>4}  {:<name_width$}  {:>8}  {:>8}  {:>9}  {:>9}  {:>7}",
+                "Rank", "Registry", "TTFB", "Total", "Tarball", "MB/s", "Success"
+            )
+            .bold()
+            .to_string(),
+        );
+        for (rank, result) in results.iter().enumerate() {
+            let Some(download) = result.download.filter(|_| result.is_success) else {
+                Self::print_failure_row(result, name_width);
+                continue;
+            };
+            Logger::line(&format!(
+                "{:>4}  {:<name_width$}  {:>8}  {:>8}  {:>9}  {:>9.2}  {:>6.0}%",
+                format!("#{}", rank + 1),
+                result.name,
+                Self::format_time(download.ttfb),
+                Self::format_time(download.total),
+                format!("{:.1}kB", download.tarball_bytes as f64 / 1000.0),
+                download.throughput,
+                result.success_ratio * 100.0
+            ));
+        }
+    }
+
+    /// Ranked results with the median time of each request phase
+    fn print_phase_table(results: &[SpeedTestResult]) {
+        let name_width = results
+            .iter()
+            .map(|r| r.name.len())
+            .chain(["Registry".len()])
+            .max()
+            .unwrap_or_default();
+
+        Logger::line(
+            &format!(
+                "{:>4}  {:<name_width$}  {:>8}  {:>8}  {:>8}  {:>8}  {:>8}  {:>8}",
+                "Rank", "Registry", "DNS", "TCP", "TLS", "TTFB", "Body", "Total"
+            )
+            .bold()
+            .to_string(),
+        );
+        for (rank, result) in results.iter().enumerate() {
+            let Some(phases) = result.phases.filter(|_| result.is_success) else {
+                Self::print_failure_row(result, name_width);
+                continue;
+            };
+            Logger::line(&format!(
+                "{:>4}  {:<name_width$}  {:>8}  {:>8}  {:>8}  {:>8}  {:>8}  {:>8}",
+                format!("#{}", rank + 1),
+                result.name,
+                Self::format_time(phases.dns),
+                Self::format_time(phases.connect),
+                phases.tls.map_or("-".to_string(), Self::format_time),
+                Self::format_time(phases.ttfb),
+                Self::format_time(phases.body),
+                Self::format_time(phases.total)
+            ));
+        }
+    }
+
+    /// Unranked row saying why a registry failed
+    fn print_failure_row(result: &SpeedTestResult, name_width: usize) {
+        Logger::line(
+            &format!(
+                "{:>4}  {:<name_width$}  {}",
+                "-",
+                result.name,
+                Self::failure_reason(result)
+            )
+            .red()
+            .to_string(),
+        );
+    }
+
+    fn failure_reason(result: &SpeedTestResult) -> String {
+        match &result.failure {
+            Some(failure) => failure.to_string(),
+            None => "failed".to_string(),
+        }
+    }
+}
+
+const SPINNER: &[&str] = &["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
+const SPINNER_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Progress of a running speed test: finished registries in rank order,
+/// then the pending ones with a spinner
+#[derive(Debug, Clone, Default)]
+pub struct LiveTable {
+    pending: Vec<String>,
+    done: Vec<SpeedTestResult>,
+    frame: usize,
+}
+
+impl LiveTable {
+    pub fn new(names: impl IntoIterator<Item = String>) -> Self {
+        LiveTable {
+            pending: names.into_iter().collect(),
+            ..Default::default()
+        }
+    }
+
+    /// Move a registry from pending to finished and re-rank
+    pub fn finish(&mut self, result: SpeedTestResult) {
+        self.pending.retain(|name| *name != result.name);
+        self.done.push(result);
+        rank_results(&mut self.done);
+    }
+
+    /// Advance the spinner
+    pub fn tick(&mut self) {
+        self.frame = (self.frame + 1) % SPINNER.len();
+    }
+
+    /// One line per registry: rank and median, failure reason, or spinner
+    pub fn rows(&self) -> Vec<String> {
+        let name_width = self
+            .pending
+            .iter()
+            .chain(self.done.iter().map(|r| &r.name))
+            .map(|name| name.len())
+            .max()
+            .unwrap_or_default();
+
+        let mut rows = Vec::new();
+        for (rank, result) in self.done.iter().enumerate() {
+            rows.push(if result.is_success {
+                format!(
+                    "{:>4}  {:<name_width$}  {}",
+                    format!("#{}", rank + 1),
+                    result.name.bold(),
+                    SpeedTester::format_time(result.response_time)
+                )
+            } else {
+                format!(
+                    "{:>4}  {:<name_width$}  {}",
+                    "✗".red(),
+                    result.name.bold(),
+                    SpeedTester::failure_reason(result).red()
+                )
+            });
+        }
+        for name in &self.pending {
+            rows.push(format!(
+                "{:>4}  {:<name_width$}  {}",
+                SPINNER[self.frame].cyan(),
+                name,
+                "testing...".dimmed()
+            ));
+        }
+        rows
+    }
 }
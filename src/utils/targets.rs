@@ -0,0 +1,501 @@
+use std::env;
+use std::io;
+use std::path::{Path, PathBuf};
+use tokio::fs;
+use toml_edit::{value, DocumentMut, InlineTable, Item, Table, Value};
+
+use super::auth::{
+    remove_auth, remove_yarn_auth, set_auth, set_yarn_auth, Credentials, RegistryAuth,
+};
+use super::error::{Error, Result};
+use super::npmrc::{npmrc_path, scope_registry_key, Npmrc};
+use super::package_manager::PackageManager;
+use super::registries::same_registry_url;
+use super::resolve::project_dir;
+use super::yarnrc::{yarnrc_path, yarnrc_yml_path, Yarnrc, YarnrcYml};
+
+/// A package manager config file that registry settings can be written to.
+///
+/// Targets are loaded with [`load_target`], edited in memory and written back
+/// with [`save_target`], so a command touching several tools can validate
+/// every edit before saving anything.
+pub trait RegistryTarget: Send {
+    fn package_manager(&self) -> PackageManager;
+
+    /// File the settings are written to
+    fn path(&self) -> &Path;
+
+    /// Set the default registry
+    fn set_registry(&mut self, url: &str);
+
+    /// Route `scope` (in its `@scope` form) to `url`
+    fn set_scope(&mut self, scope: &str, url: &str);
+
+    /// Returns `false` if the scope was not configured
+    fn remove_scope(&mut self, scope: &str) -> bool;
+
+    /// Store credentials for a registry URL, returning the key they were
+    /// written under
+    fn set_auth(&mut self, url: &str, auth: &RegistryAuth) -> Result<String>;
+
+    /// Remove the credentials for a registry URL, returning how many
+    /// settings were removed
+    fn remove_auth(&mut self, url: &str) -> usize;
+
+    /// File content to write back
+    fn render(&self) -> String;
+}
+
+/// Global or local config file of a single package manager; local files
+/// are at the project root of the working directory
+pub fn target_path(pm: PackageManager, local: bool) -> Result<PathBuf> {
+    if local {
+        return local_target_path(pm, &project_dir()?);
+    }
+    match pm {
+        PackageManager::Npm => npmrc_path(false),
+        PackageManager::Pnpm => pnpm_rc_path(),
+        PackageManager::Yarn => yarnrc_path(false),
+        PackageManager::YarnBerry => yarnrc_yml_path(false),
+        PackageManager::Bun => bunfig_path(false),
+        PackageManager::All => Err(unresolved_all()),
+    }
+}
+
+/// Local config file of a single package manager in the project at `root`
+pub fn local_target_path(pm: PackageManager, root: &Path) -> Result<PathBuf> {
+    let file = match pm {
+        PackageManager::Npm | PackageManager::Pnpm => ".npmrc",
+        PackageManager::Yarn => ".yarnrc",
+        PackageManager::YarnBerry => ".yarnrc.yml",
+        PackageManager::Bun => "bunfig.toml",
+        PackageManager::All => return Err(unresolved_all()),
+    };
+    Ok(root.join(file))
+}
+
+/// Load the global or local config of a single package manager
+pub async fn load_target(pm: PackageManager, local: bool) -> Result<Box<dyn RegistryTarget>> {
+    load_target_at(pm, target_path(pm, local)?).await
+}
+
+/// Load a package manager config from an explicit path
+pub async fn load_target_at(pm: PackageManager, path: PathBuf) -> Result<Box<dyn RegistryTarget>> {
+    Ok(match pm {
+        PackageManager::Npm | PackageManager::Pnpm => Box::new(NpmrcTarget {
+            pm,
+            npmrc: Npmrc::load(&path).await?,
+            path,
+        }),
+        PackageManager::Yarn => Box::new(YarnTarget {
+            yarnrc: Yarnrc::load(&path).await?,
+            path,
+        }),
+        PackageManager::YarnBerry => Box::new(YarnBerryTarget {
+            yarnrc: YarnrcYml::load(&path).await?,
+            path,
+        }),
+        PackageManager::Bun => Box::new(BunTarget::load(path).await?),
+        PackageManager::All => return Err(unresolved_all()),
+    })
+}
+
+/// Write a target back to its file, creating parent directories as needed
+pub async fn save_target(target: &dyn RegistryTarget) -> Result<()> {
+    let path = target.path();
+    let io_error = |source| match target.package_manager() {
+        PackageManager::Npm | PackageManager::Pnpm => Error::Npmrc {
+            path: path.to_path_buf(),
+            source,
+        },
+        _ => Error::PackageManagerConfig {
+            path: path.to_path_buf(),
+            source,
+        },
+    };
+
+    if let Some(parent) = path.parent() {
+        if !parent.as_os_str().is_empty() && !parent.exists() {
+            fs::create_dir_all(parent).await.map_err(io_error)?;
+        }
+    }
+    fs::write(path, target.render()).await.map_err(io_error)
+}
+
+/// pnpm's global `rc` file, following `pnpm config --global`
+pub fn pnpm_rc_path() -> Result<PathBuf> {
+    if let Some(dir) = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+    {
+        return Ok(dir.join("pnpm").join("rc"));
+    }
+
+    let home = dirs::home_dir().ok_or_else(|| Error::NotFound("Home directory".to_string()))?;
+    let config_dir = if cfg!(windows) {
+        dirs::data_local_dir()
+            .unwrap_or_else(|| home.join("AppData").join("Local"))
+            .join("pnpm")
+            .join("config")
+    } else if cfg!(target_os = "macos") {
+        home.join("Library").join("Preferences").join("pnpm")
+    } else {
+        home.join(".config").join("pnpm")
+    };
+    Ok(config_dir.join("rc"))
+}
+
+/// Local `bunfig.toml`, or the global `.bunfig.toml` in `$XDG_CONFIG_HOME`
+/// or the home directory
+pub fn bunfig_path(is_local: bool) -> Result<PathBuf> {
+    if is_local {
+        return Ok(project_dir()?.join("bunfig.toml"));
+    }
+
+    if let Some(dir) = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+    {
+        return Ok(dir.join(".bunfig.toml"));
+    }
+    dirs::home_dir()
+        .map(|home| home.join(".bunfig.toml"))
+        .ok_or_else(|| Error::NotFound("Home directory".to_string()))
+}
+
+fn unresolved_all() -> Error {
+    Error::Validation("`all` has to be resolved to the detected package managers first".to_string())
+}
+
+/// `.npmrc`-style ini files, used by npm and pnpm
+struct NpmrcTarget {
+    pm: PackageManager,
+    path: PathBuf,
+    npmrc: Npmrc,
+}
+
+impl RegistryTarget for NpmrcTarget {
+    fn package_manager(&self) -> PackageManager {
+        self.pm
+    }
+
+    fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn set_registry(&mut self, url: &str) {
+        self.npmrc.set("registry", url);
+    }
+
+    fn set_scope(&mut self, scope: &str, url: &str) {
+        self.npmrc.set(&scope_registry_key(scope), url);
+    }
+
+    fn remove_scope(&mut self, scope: &str) -> bool {
+        self.npmrc.remove(&scope_registry_key(scope))
+    }
+
+    fn set_auth(&mut self, url: &str, auth: &RegistryAuth) -> Result<String> {
+        set_auth(&mut self.npmrc, url, auth)
+            .ok_or_else(|| Error::Validation(format!("Invalid registry URL: {}", url)))
+    }
+
+    fn remove_auth(&mut self, url: &str) -> usize {
+        remove_auth(&mut self.npmrc, url).len()
+    }
+
+    fn render(&self) -> String {
+        self.npmrc.to_string()
+    }
+}
+
+/// Yarn 1's `.yarnrc`
+struct YarnTarget {
+    path: PathBuf,
+    yarnrc: Yarnrc,
+}
+
+impl RegistryTarget for YarnTarget {
+    fn package_manager(&self) -> PackageManager {
+        PackageManager::Yarn
+    }
+
+    fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn set_registry(&mut self, url: &str) {
+        self.yarnrc.set("registry", url);
+    }
+
+    fn set_scope(&mut self, scope: &str, url: &str) {
+        self.yarnrc.set(&scope_registry_key(scope), url);
+    }
+
+    fn remove_scope(&mut self, scope: &str) -> bool {
+        self.yarnrc.remove(&scope_registry_key(scope))
+    }
+
+    fn set_auth(&mut self, _url: &str, _auth: &RegistryAuth) -> Result<String> {
+        // Yarn 1 takes credentials from `.npmrc`, so there is nothing to write
+        Err(Error::Validation(
+            "Yarn 1 reads credentials from .npmrc, use --pm npm instead".to_string(),
+        ))
+    }
+
+    fn remove_auth(&mut self, _url: &str) -> usize {
+        0
+    }
+
+    fn render(&self) -> String {
+        self.yarnrc.to_string()
+    }
+}
+
+/// Yarn 2+'s `.yarnrc.yml`
+struct YarnBerryTarget {
+    path: PathBuf,
+    yarnrc: YarnrcYml,
+}
+
+impl RegistryTarget for YarnBerryTarget {
+    fn package_manager(&self) -> PackageManager {
+        PackageManager::YarnBerry
+    }
+
+    fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn set_registry(&mut self, url: &str) {
+        self.yarnrc.set(&["npmRegistryServer"], url);
+    }
+
+    fn set_scope(&mut self, scope: &str, url: &str) {
+        self.yarnrc
+            .set(&["npmScopes", bare_scope(scope), "npmRegistryServer"], url);
+    }
+
+    fn remove_scope(&mut self, scope: &str) -> bool {
+        self.yarnrc.remove(&["npmScopes", bare_scope(scope)])
+    }
+
+    fn set_auth(&mut self, url: &str, auth: &RegistryAuth) -> Result<String> {
+        Ok(set_yarn_auth(&mut self.yarnrc, url, auth))
+    }
+
+    fn remove_auth(&mut self, url: &str) -> usize {
+        usize::from(remove_yarn_auth(&mut self.yarnrc, url))
+    }
+
+    fn render(&self) -> String {
+        self.yarnrc.to_string()
+    }
+}
+
+/// Bun's `bunfig.toml`: `[install] registry` and `[install.scopes]`
+struct BunTarget {
+    path: PathBuf,
+    doc: DocumentMut,
+}
+
+impl BunTarget {
+    async fn load(path: PathBuf) -> Result<Self> {
+        let content = match fs::read_to_string(&path).await {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(source) => return Err(Error::PackageManagerConfig { path, source }),
+        };
+        let doc = content
+            .parse::<DocumentMut>()
+            .map_err(|e| Error::ConfigParse {
+                path: path.clone(),
+                message: e.to_string(),
+            })?;
+        Ok(Self { path, doc })
+    }
+
+    fn install(&mut self) -> &mut Table {
+        let install = self.doc.entry("install").or_insert_with(|| {
+            let mut table = Table::new();
+            table.set_implicit(true);
+            Item::Table(table)
+        });
+        into_table(install);
+        install.as_table_mut().expect("install is a table")
+    }
+
+    fn scopes(&mut self) -> &mut Table {
+        let scopes = self
+            .install()
+            .entry("scopes")
+            .or_insert_with(|| Item::Table(Table::new()));
+        into_table(scopes);
+        scopes.as_table_mut().expect("scopes is a table")
+    }
+
+    /// `install.registry` and every `install.scopes` entry pointing at `url`
+    fn entries_for(&mut self, url: &str) -> Vec<(String, &mut Item)> {
+        let mut entries = Vec::new();
+        let Some(install) = self.doc.get_mut("install").and_then(Item::as_table_mut) else {
+            return entries;
+        };
+        let mut scope_entries = Vec::new();
+        for (key, item) in install.iter_mut() {
+            if key == "registry"
+                && entry_url(item).is_some_and(|found| same_registry_url(&found, url))
+            {
+                entries.push(("install.registry".to_string(), item));
+            } else if key == "scopes" {
+                if let Some(scopes) = item.as_table_like_mut() {
+                    for (scope, item) in scopes.iter_mut() {
+                        if entry_url(item).is_some_and(|found| same_registry_url(&found, url)) {
+                            scope_entries.push((format!("install.scopes.{}", scope), item));
+                        }
+                    }
+                }
+            }
+        }
+        entries.extend(scope_entries);
+        entries
+    }
+}
+
+impl RegistryTarget for BunTarget {
+    fn package_manager(&self) -> PackageManager {
+        PackageManager::Bun
+    }
+
+    fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn set_registry(&mut self, url: &str) {
+        set_entry_url(self.install().entry("registry").or_insert(Item::None), url);
+    }
+
+    fn set_scope(&mut self, scope: &str, url: &str) {
+        let key = bare_scope(scope);
+        let scopes = self.scopes();
+        // Bun accepts scopes with or without their `@`
+        if !scopes.contains_key(key) && scopes.contains_key(scope) {
+            set_entry_url(&mut scopes[scope], url);
+        } else {
+            set_entry_url(scopes.entry(key).or_insert(Item::None), url);
+        }
+    }
+
+    fn remove_scope(&mut self, scope: &str) -> bool {
+        let Some(scopes) = self
+            .doc
+            .get_mut("install")
+            .and_then(|install| install.get_mut("scopes"))
+            .and_then(Item::as_table_like_mut)
+        else {
+            return false;
+        };
+        let removed = scopes.remove(bare_scope(scope)).is_some() | scopes.remove(scope).is_some();
+        if scopes.is_empty() {
+            if let Some(install) = self.doc.get_mut("install").and_then(Item::as_table_mut) {
+                install.remove("scopes");
+            }
+        }
+        removed
+    }
+
+    fn set_auth(&mut self, url: &str, auth: &RegistryAuth) -> Result<String> {
+        let entries = self.entries_for(url);
+        if entries.is_empty() {
+            return Err(Error::Validation(format!(
+                "{} is not used in bunfig.toml, run `use` or `set-scope` with --pm bun first",
+                url
+            )));
+        }
+
+        // Bun has no always-auth setting; credentials are always sent
+        let mut keys = Vec::new();
+        for (key, item) in entries {
+            let mut table = InlineTable::new();
+            let entry_url = entry_url(item).unwrap_or_else(|| url.to_string());
+            table.insert("url", Value::from(entry_url));
+            match &auth.credentials {
+                Some(Credentials::Token(token)) => {
+                    table.insert("token", Value::from(token.as_str()));
+                }
+                Some(Credentials::Basic { username, password }) => {
+                    table.insert("username", Value::from(username.as_str()));
+                    table.insert("password", Value::from(password.as_str()));
+                }
+                Some(Credentials::Legacy(_)) | None => continue,
+            }
+            *item = value(table);
+            keys.push(key);
+        }
+        if keys.is_empty() {
+            return Err(Error::Validation(
+                "Bun only supports token or username/password credentials".to_string(),
+            ));
+        }
+        Ok(keys.join(", "))
+    }
+
+    fn remove_auth(&mut self, url: &str) -> usize {
+        let mut removed = 0;
+        for (_, item) in self.entries_for(url) {
+            let has_credentials = item.as_table_like().is_some_and(|table| {
+                ["token", "username", "password"]
+                    .iter()
+                    .any(|key| table.contains_key(key))
+            });
+            if has_credentials {
+                let url = entry_url(item).unwrap_or_default();
+                *item = value(url);
+                removed += 1;
+            }
+        }
+        removed
+    }
+
+    fn render(&self) -> String {
+        self.doc.to_string()
+    }
+}
+
+/// Turn an inline table into a regular `[table]`, or replace any other value
+fn into_table(item: &mut Item) {
+    if let Some(inline) = item.as_inline_table() {
+        *item = Item::Table(inline.clone().into_table());
+    } else if !item.is_table() {
+        *item = Item::Table(Table::new());
+    }
+}
+
+/// URL of a bunfig registry entry, written either as a string or as a
+/// table with a `url` key
+fn entry_url(item: &Item) -> Option<String> {
+    match item.as_str() {
+        Some(url) => Some(url.to_string()),
+        None => item
+            .as_table_like()?
+            .get("url")?
+            .as_str()
+            .map(str::to_string),
+    }
+}
+
+/// Point a bunfig registry entry at `url`, keeping any credentials that are
+/// stored with it
+fn set_entry_url(item: &mut Item, url: &str) {
+    if let Some(table) = item.as_table_like_mut() {
+        if table.contains_key("url") {
+            table.insert("url", value(url));
+            return;
+        }
+    }
+    *item = value(url);
+}
+
+/// Yarn and Bun key scopes by their name without the `@`
+fn bare_scope(scope: &str) -> &str {
+    scope.trim_start_matches('@')
+}
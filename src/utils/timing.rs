@@ -0,0 +1,475 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::Serialize;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::{lookup_host, TcpStream};
+use tokio_native_tls::{native_tls, TlsConnector};
+use url::Url;
+
+use super::proxy::ProxyConfig;
+use super::speed_test::{FailureKind, LatencyStats, TestFailure};
+
+/// Redirects followed before a request fails, as many as reqwest follows
+const MAX_REDIRECTS: usize = 10;
+
+/// Time spent in each phase of one request, in seconds. The request opens
+/// its own connection, so every phase is measured.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct PhaseTimings {
+    /// Resolving the host name
+    pub dns: f64,
+    /// Opening the TCP connection
+    pub connect: f64,
+    /// TLS handshake, `None` for plain http
+    pub tls: Option<f64>,
+    /// From sending the request to the first response byte
+    pub ttfb: f64,
+    /// Reading the rest of the response
+    pub body: f64,
+    /// Whole request, start to end
+    pub total: f64,
+}
+
+impl PhaseTimings {
+    /// Median of every phase over several requests
+    pub fn median(samples: &[PhaseTimings]) -> Option<Self> {
+        if samples.is_empty() {
+            return None;
+        }
+        let median = |value: fn(&PhaseTimings) -> f64| {
+            LatencyStats::from_samples(&samples.iter().map(value).collect::<Vec<_>>()).median
+        };
+        Some(PhaseTimings {
+            dns: median(|t| t.dns),
+            connect: median(|t| t.connect),
+            tls: samples[0]
+                .tls
+                .map(|_| median(|t| t.tls.unwrap_or_default())),
+            ttfb: median(|t| t.ttfb),
+            body: median(|t| t.body),
+            total: median(|t| t.total),
+        })
+    }
+
+    /// Add the phases of a request made after this one, such as a redirect
+    fn add(&mut self, other: &PhaseTimings) {
+        self.dns += other.dns;
+        self.connect += other.connect;
+        self.tls = match (self.tls, other.tls) {
+            (None, None) => None,
+            (tls, other) => Some(tls.unwrap_or_default() + other.unwrap_or_default()),
+        };
+        self.ttfb += other.ttfb;
+        self.body += other.body;
+        self.total += other.total;
+    }
+}
+
+/// One request and response of a measurement
+struct Hop {
+    status: u16,
+    /// Target of a redirect response
+    location: Option<String>,
+    timings: PhaseTimings,
+}
+
+/// GET `url` over a fresh connection, timing each phase. Redirects are
+/// followed, up to `MAX_REDIRECTS`, each over its own connection and with
+/// its phases added to the total. The whole request fails with
+/// `FailureKind::Timeout` once `timeout` has passed.
+pub async fn measure_phases(
+    url: &str,
+    timeout: Duration,
+) -> Result<(u16, PhaseTimings), TestFailure> {
+    measure_phases_via(url, timeout, &ProxyConfig::default()).await
+}
+
+/// Like `measure_phases`, going through the HTTP proxy `proxy` picks for
+/// each URL. DNS and connect then time reaching the proxy, and connect
+/// includes opening the tunnel for https.
+pub async fn measure_phases_via(
+    url: &str,
+    timeout: Duration,
+    proxy: &ProxyConfig,
+) -> Result<(u16, PhaseTimings), TestFailure> {
+    match tokio::time::timeout(timeout, follow(url, proxy)).await {
+        Ok(result) => result,
+        Err(_) => Err(failure(
+            FailureKind::Timeout,
+            format!("no response within {}ms", timeout.as_millis()),
+        )),
+    }
+}
+
+/// Request `url` and the redirects it leads to
+async fn follow(url: &str, proxy: &ProxyConfig) -> Result<(u16, PhaseTimings), TestFailure> {
+    let mut url = Url::parse(url).map_err(|e| failure(FailureKind::Connect, e.to_string()))?;
+    let mut timings = PhaseTimings::default();
+    let mut redirects = 0;
+    loop {
+        let hop = measure(&url, proxy.proxy_for(&url).as_ref()).await?;
+        timings.add(&hop.timings);
+        let Some(location) = hop.location else {
+            return check_status(hop.status, timings);
+        };
+        if redirects == MAX_REDIRECTS {
+            return Err(TestFailure {
+                kind: FailureKind::Http,
+                status: Some(hop.status),
+                message: format!(
+                    "{}: more than {} redirects",
+                    FailureKind::Http,
+                    MAX_REDIRECTS
+                ),
+            });
+        }
+        url = url
+            .join(&location)
+            .map_err(|e| failure(FailureKind::Http, format!("invalid redirect: {}", e)))?;
+        redirects += 1;
+    }
+}
+
+fn check_status(status: u16, timings: PhaseTimings) -> Result<(u16, PhaseTimings), TestFailure> {
+    if !(200..300).contains(&status) {
+        // Same wording as the reqwest errors, e.g. `HTTP 404 Not Found`
+        let reason = reqwest::StatusCode::from_u16(status)
+            .map_or_else(|_| status.to_string(), |status| status.to_string());
+        return Err(TestFailure {
+            kind: FailureKind::Http,
+            status: Some(status),
+            message: format!("HTTP {}", reason),
+        });
+    }
+    Ok((status, timings))
+}
+
+async fn measure(url: &Url, proxy: Option<&Url>) -> Result<Hop, TestFailure> {
+    let host = url
+        .host_str()
+        .ok_or_else(|| failure(FailureKind::Connect, "missing host".to_string()))?
+        .to_string();
+    let port = url.port_or_known_default().unwrap_or(80);
+    let (connect_host, connect_port) = match proxy {
+        Some(proxy) if proxy.scheme() != "http" => {
+            return Err(failure(
+                FailureKind::Connect,
+                format!("unsupported proxy scheme {}", proxy.scheme()),
+            ))
+        }
+        Some(proxy) => (
+            proxy
+                .host_str()
+                .ok_or_else(|| failure(FailureKind::Connect, "missing proxy host".to_string()))?
+                .to_string(),
+            proxy.port_or_known_default().unwrap_or(80),
+        ),
+        None => (host.clone(), port),
+    };
+
+    let start = Instant::now();
+    let address = lookup_host((connect_host.trim_matches(['[', ']']), connect_port))
+        .await
+        .map_err(|e| failure(FailureKind::Dns, e.to_string()))?
+        .next()
+        .ok_or_else(|| failure(FailureKind::Dns, format!("no address for {}", connect_host)))?;
+    let dns = start.elapsed().as_secs_f64();
+
+    let connect_start = Instant::now();
+    let mut stream = TcpStream::connect(address)
+        .await
+        .map_err(|e| failure(FailureKind::Connect, e.to_string()))?;
+    if let Some(proxy) = proxy.filter(|_| url.scheme() == "https") {
+        open_tunnel(&mut stream, &format!("{}:{}", host, port), proxy).await?;
+    }
+    let connect = connect_start.elapsed().as_secs_f64();
+
+    // Plain http goes to the proxy as an absolute-form request
+    let (target, proxy_authorization) = match proxy {
+        Some(proxy) if url.scheme() == "http" => (url.to_string(), proxy_authorization(proxy)),
+        _ => (
+            url[url::Position::BeforePath..url::Position::AfterQuery].to_string(),
+            String::new(),
+        ),
+    };
+    let request = format!(
+        "GET {} HTTP/1.1\r\nHost: {}\r\n{}User-Agent: rust-nrm\r\nAccept: */*\r\nConnection: close\r\n\r\n",
+        target,
+        &url[url::Position::BeforeHost..url::Position::AfterPort],
+        proxy_authorization,
+    );
+
+    let (response, tls, ttfb, body) = if url.scheme() == "https" {
+        let tls_start = Instant::now();
+        let connector = native_tls::TlsConnector::new()
+            .map(TlsConnector::from)
+            .map_err(|e| failure(FailureKind::Tls, e.to_string()))?;
+        let stream = connector
+            .connect(&host, stream)
+            .await
+            .map_err(|e| failure(FailureKind::Tls, e.to_string()))?;
+        let tls = tls_start.elapsed().as_secs_f64();
+        let (response, ttfb, body) = exchange(stream, &request).await?;
+        (response, Some(tls), ttfb, body)
+    } else {
+        let (response, ttfb, body) = exchange(stream, &request).await?;
+        (response, None, ttfb, body)
+    };
+
+    let timings = PhaseTimings {
+        dns,
+        connect,
+        tls,
+        ttfb,
+        body,
+        total: start.elapsed().as_secs_f64(),
+    };
+    let status = parse_status(&response)
+        .ok_or_else(|| failure(FailureKind::Http, "invalid HTTP response".to_string()))?;
+    let location = match status {
+        301 | 302 | 303 | 307 | 308 => header(&response, "location"),
+        _ => None,
+    };
+    Ok(Hop {
+        status,
+        location,
+        timings,
+    })
+}
+
+/// Ask an HTTP proxy to open a tunnel to `authority` (`host:port`)
+async fn open_tunnel(
+    stream: &mut TcpStream,
+    authority: &str,
+    proxy: &Url,
+) -> Result<(), TestFailure> {
+    let connect_error = |e: std::io::Error| failure(FailureKind::Connect, e.to_string());
+    let request = format!(
+        "CONNECT {0} HTTP/1.1\r\nHost: {0}\r\n{1}\r\n",
+        authority,
+        proxy_authorization(proxy)
+    );
+    stream
+        .write_all(request.as_bytes())
+        .await
+        .map_err(connect_error)?;
+
+    // Nothing follows the proxy's answer until the tunnel is used
+    let mut response = Vec::new();
+    let mut buf = [0; 1024];
+    while !response.ends_with(b"\r\n\r\n") {
+        let read = stream.read(&mut buf).await.map_err(connect_error)?;
+        if read == 0 {
+            return Err(failure(
+                FailureKind::Connect,
+                "proxy closed the connection".to_string(),
+            ));
+        }
+        response.extend_from_slice(&buf[..read]);
+    }
+    let status = parse_status(&response);
+    if !status.is_some_and(|status| (200..300).contains(&status)) {
+        return Err(failure(
+            FailureKind::Connect,
+            format!(
+                "proxy refused the tunnel with HTTP {}",
+                status.map_or("?".to_string(), |status| status.to_string())
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// `Proxy-Authorization` header line for credentials in the proxy URL
+fn proxy_authorization(proxy: &Url) -> String {
+    if proxy.username().is_empty() {
+        return String::new();
+    }
+    let credentials = format!(
+        "{}:{}",
+        proxy.username(),
+        proxy.password().unwrap_or_default()
+    );
+    format!(
+        "Proxy-Authorization: Basic {}\r\n",
+        STANDARD.encode(credentials)
+    )
+}
+
+/// Send `request` and read the whole response, returning it with the time
+/// to the first byte and the time to read the rest
+async fn exchange<S>(mut stream: S, request: &str) -> Result<(Vec<u8>, f64, f64), TestFailure>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let read_error = |e: std::io::Error| failure(FailureKind::Http, e.to_string());
+    let start = Instant::now();
+    stream
+        .write_all(request.as_bytes())
+        .await
+        .map_err(read_error)?;
+
+    let mut response = Vec::new();
+    let mut buf = [0; 8192];
+    let read = stream.read(&mut buf).await.map_err(read_error)?;
+    let ttfb = start.elapsed().as_secs_f64();
+    response.extend_from_slice(&buf[..read]);
+
+    let body_start = Instant::now();
+    let mut progress = ResponseProgress::default();
+    let mut read = read;
+    while read > 0 && !progress.is_complete(&response) {
+        read = stream.read(&mut buf).await.map_err(read_error)?;
+        response.extend_from_slice(&buf[..read]);
+    }
+    let body = body_start.elapsed().as_secs_f64();
+    Ok((response, ttfb, body))
+}
+
+/// Status code from the status line of an HTTP response
+fn parse_status(response: &[u8]) -> Option<u16> {
+    String::from_utf8_lossy(&response[..response.len().min(64)])
+        .split_whitespace()
+        .nth(1)
+        .and_then(|status| status.parse().ok())
+}
+
+/// Value of the header `name` (lowercase) in an HTTP response
+fn header(response: &[u8], name: &str) -> Option<String> {
+    let header_end = find(response, b"\r\n\r\n")?;
+    String::from_utf8_lossy(&response[..header_end])
+        .lines()
+        .skip(1)
+        .find_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            key.trim()
+                .eq_ignore_ascii_case(name)
+                .then(|| value.trim().to_string())
+        })
+}
+
+/// How the end of a response body is found
+#[derive(Debug, Clone, Copy)]
+enum Framing {
+    /// The body ends at this offset of the response
+    Length(usize),
+    /// Chunked body; `next` is the offset of the next chunk size line, or of
+    /// the next trailer line once the last chunk was seen
+    Chunked { next: usize, trailers: bool },
+    /// The server ends the body by closing the connection
+    UntilClose,
+}
+
+/// Tracks a response as it arrives. The headers are parsed once, then only
+/// the new bytes of the body are looked at, so reading stays linear in the
+/// size of the response.
+#[derive(Debug, Default)]
+struct ResponseProgress {
+    /// Where to resume looking for the end of the headers
+    scanned: usize,
+    framing: Option<Framing>,
+}
+
+impl ResponseProgress {
+    /// Whether the response is complete according to its Content-Length or
+    /// chunked encoding; otherwise it ends when the server closes the
+    /// connection
+    fn is_complete(&mut self, response: &[u8]) -> bool {
+        let framing = match self.framing {
+            Some(framing) => framing,
+            None => {
+                // The terminator may straddle the previous read
+                let from = self.scanned.saturating_sub(3);
+                let Some(end) = find(&response[from..], b"\r\n\r\n") else {
+                    self.scanned = response.len();
+                    return false;
+                };
+                let framing = parse_framing(&response[..from + end], from + end + 4);
+                self.framing = Some(framing);
+                framing
+            }
+        };
+
+        match framing {
+            Framing::Length(end) => response.len() >= end,
+            Framing::Chunked { next, trailers } => {
+                let (framing, complete) = advance_chunks(response, next, trailers);
+                self.framing = Some(framing);
+                complete
+            }
+            Framing::UntilClose => false,
+        }
+    }
+}
+
+/// Framing announced by the response headers, for a body starting at
+/// `body_start`
+fn parse_framing(headers: &[u8], body_start: usize) -> Framing {
+    let headers = String::from_utf8_lossy(headers).to_lowercase();
+    for line in headers.lines() {
+        if let Some(length) = line.strip_prefix("content-length:") {
+            return match length.trim().parse::<usize>() {
+                Ok(length) => Framing::Length(body_start + length),
+                Err(_) => Framing::UntilClose,
+            };
+        }
+        if line.starts_with("transfer-encoding:") && line.contains("chunked") {
+            return Framing::Chunked {
+                next: body_start,
+                trailers: false,
+            };
+        }
+    }
+    Framing::UntilClose
+}
+
+/// Walk the chunks that arrived since `next`, returning where to resume and
+/// whether the last chunk and the empty line after the trailers were seen.
+/// Chunk data is skipped by its size, so data that happens to contain
+/// `0\r\n\r\n` does not end the body. A malformed size line is left for
+/// the server to end by closing the connection.
+fn advance_chunks(response: &[u8], mut next: usize, mut trailers: bool) -> (Framing, bool) {
+    loop {
+        let Some(end) = response.get(next..).and_then(|rest| find(rest, b"\r\n")) else {
+            return (Framing::Chunked { next, trailers }, false);
+        };
+        let line = &response[next..next + end];
+        if trailers {
+            if line.is_empty() {
+                return (Framing::Chunked { next, trailers }, true);
+            }
+            next += end + 2;
+            continue;
+        }
+
+        let size = std::str::from_utf8(line).ok().and_then(|line| {
+            // Chunk extensions follow a `;`
+            let size = line.split(';').next()?.trim();
+            usize::from_str_radix(size, 16).ok()
+        });
+        let Some(size) = size else {
+            return (Framing::UntilClose, false);
+        };
+        next += end + 2;
+        if size == 0 {
+            trailers = true;
+        } else {
+            // The data and its line break, which may not have arrived yet
+            next += size + 2;
+        }
+    }
+}
+
+/// Offset of the first occurrence of `needle` in `data`
+fn find(data: &[u8], needle: &[u8]) -> Option<usize> {
+    data.windows(needle.len()).position(|w| w == needle)
+}
+
+fn failure(kind: FailureKind, message: String) -> TestFailure {
+    TestFailure {
+        kind,
+        status: None,
+        message: format!("{}: {}", kind, message),
+    }
+}
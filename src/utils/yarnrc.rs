@@ -0,0 +1,608 @@
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use tokio::fs;
+
+use super::error::{Error, Result};
+use super::resolve::project_dir;
+
+/// Indentation used for new nested keys when the file has none to copy
+const DEFAULT_INDENT: usize = 2;
+
+/// A `key: value` line of a YAML mapping
+#[derive(Debug, Clone, PartialEq)]
+struct YamlKey {
+    line: usize,
+    indent: usize,
+    /// Key as written, including any quotes
+    raw_key: String,
+    key: String,
+    /// Scalar after the colon, still quoted; empty for nested mappings
+    raw_value: String,
+    /// Trailing `# comment`, kept when the value is replaced
+    comment: Option<String>,
+}
+
+/// Yarn Berry's `.yarnrc.yml`, edited line by line.
+///
+/// Only the block-mapping subset of YAML that Yarn writes is understood;
+/// every line that is not touched through `set`/`remove` (comments, lists,
+/// other settings) is written back unchanged.
+#[derive(Debug, Clone, PartialEq)]
+pub struct YarnrcYml {
+    lines: Vec<String>,
+    crlf: bool,
+    trailing_newline: bool,
+}
+
+impl Default for YarnrcYml {
+    fn default() -> Self {
+        Self {
+            lines: Vec::new(),
+            crlf: false,
+            trailing_newline: true,
+        }
+    }
+}
+
+impl YarnrcYml {
+    pub fn parse(content: &str) -> Self {
+        let crlf = content.contains("\r\n");
+        let trailing_newline = content.is_empty() || content.ends_with('\n');
+        let body = content.strip_suffix('\n').unwrap_or(content);
+
+        let lines = if content.is_empty() {
+            Vec::new()
+        } else {
+            body.split('\n')
+                .map(|line| line.strip_suffix('\r').unwrap_or(line).to_string())
+                .collect()
+        };
+
+        Self {
+            lines,
+            crlf,
+            trailing_newline,
+        }
+    }
+
+    /// Read a `.yarnrc.yml` file, returning an empty document if it does not exist
+    pub async fn load(path: &Path) -> Result<Self> {
+        match fs::read_to_string(path).await {
+            Ok(content) => Ok(Self::parse(&content)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(source) => Err(Error::PackageManagerConfig {
+                path: path.to_path_buf(),
+                source,
+            }),
+        }
+    }
+
+    pub async fn save(&self, path: &Path) -> Result<()> {
+        fs::write(path, self.to_string())
+            .await
+            .map_err(|source| Error::PackageManagerConfig {
+                path: path.to_path_buf(),
+                source,
+            })
+    }
+
+    /// Scalar value at a key path, e.g. `["npmScopes", "company", "npmRegistryServer"]`
+    pub fn get(&self, path: &[&str]) -> Option<String> {
+        let key = self.find(path)?;
+        if key.raw_value.is_empty() {
+            return None;
+        }
+        Some(unquote_scalar(&key.raw_value))
+    }
+
+    /// Set a string value, creating any missing parent mappings
+    pub fn set(&mut self, path: &[&str], value: &str) {
+        self.set_raw(path, &quote_scalar(value));
+    }
+
+    /// Set a boolean value, creating any missing parent mappings
+    pub fn set_bool(&mut self, path: &[&str], value: bool) {
+        self.set_raw(path, if value { "true" } else { "false" });
+    }
+
+    fn set_raw(&mut self, path: &[&str], raw_value: &str) {
+        let Some((last, parents)) = path.split_last() else {
+            return;
+        };
+
+        // Walk down the existing mappings as far as the path goes
+        let mut start = 0;
+        let mut end = self.lines.len();
+        let mut indent = self.child_indent(start, end, None);
+        for (depth, key) in parents.iter().enumerate() {
+            let Some(found) = self.find_in(start, end, indent, key) else {
+                self.insert_path(end, indent, &path[depth..], raw_value);
+                return;
+            };
+            if !found.raw_value.is_empty() {
+                // A scalar (or `{}`) where a mapping is needed is replaced
+                self.lines[found.line] = format!(
+                    "{}{}:{}",
+                    " ".repeat(found.indent),
+                    found.raw_key,
+                    render_comment(&found.comment)
+                );
+            }
+            start = found.line + 1;
+            end = self.block_end(found.line, found.indent);
+            indent = self.child_indent(start, end, Some(found.indent));
+        }
+
+        match self.find_in(start, end, indent, last) {
+            Some(found) => {
+                let block_end = self.block_end(found.line, found.indent);
+                self.lines.drain(found.line + 1..block_end);
+                self.lines[found.line] = format!(
+                    "{}{}: {}{}",
+                    " ".repeat(found.indent),
+                    found.raw_key,
+                    raw_value,
+                    render_comment(&found.comment)
+                );
+            }
+            None => self.insert_path(end, indent, &[last], raw_value),
+        }
+    }
+
+    /// Remove a key and everything nested under it. Parent mappings that end
+    /// up empty are removed too. Returns `false` if the key was not present.
+    pub fn remove(&mut self, path: &[&str]) -> bool {
+        let Some(found) = self.find(path) else {
+            return false;
+        };
+        let block_end = self.block_end(found.line, found.indent);
+        self.lines.drain(found.line..block_end);
+
+        if let Some((_, parents)) = path.split_last() {
+            if let Some(parent) = self.find(parents) {
+                let parent_end = self.block_end(parent.line, parent.indent);
+                if parent.raw_value.is_empty() && parent_end == parent.line + 1 {
+                    self.remove(parents);
+                }
+            }
+        }
+        true
+    }
+
+    fn find(&self, path: &[&str]) -> Option<YamlKey> {
+        let mut start = 0;
+        let mut end = self.lines.len();
+        let mut indent = self.child_indent(start, end, None);
+        let mut found = None;
+        for key in path {
+            let key = self.find_in(start, end, indent, key)?;
+            start = key.line + 1;
+            end = self.block_end(key.line, key.indent);
+            indent = self.child_indent(start, end, Some(key.indent));
+            found = Some(key);
+        }
+        found
+    }
+
+    fn find_in(&self, start: usize, end: usize, indent: usize, key: &str) -> Option<YamlKey> {
+        (start..end)
+            .filter_map(|line| parse_key(line, &self.lines[line]))
+            .find(|found| found.indent == indent && found.key == key)
+    }
+
+    /// Index just past the last line that belongs to the block opened at `line`
+    fn block_end(&self, line: usize, indent: usize) -> usize {
+        let mut end = line + 1;
+        for index in line + 1..self.lines.len() {
+            let text = &self.lines[index];
+            let trimmed = text.trim_start();
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+            let line_indent = text.len() - trimmed.len();
+            // Lists are often written at the same indentation as their key
+            if line_indent > indent || (line_indent == indent && trimmed.starts_with('-')) {
+                end = index + 1;
+            } else {
+                break;
+            }
+        }
+        end
+    }
+
+    /// Indentation of the children in `start..end`, or one level deeper than
+    /// `parent` when the mapping is still empty
+    fn child_indent(&self, start: usize, end: usize, parent: Option<usize>) -> usize {
+        let existing = (start..end)
+            .filter_map(|line| parse_key(line, &self.lines[line]))
+            .map(|key| key.indent)
+            .find(|indent| parent.is_none_or(|parent| *indent > parent));
+        match (existing, parent) {
+            (Some(indent), _) => indent,
+            (None, None) => 0,
+            (None, Some(parent)) => parent + self.indent_unit(),
+        }
+    }
+
+    /// Indentation step of the first nested mapping, so new keys match the style
+    fn indent_unit(&self) -> usize {
+        let content = self.lines.iter().enumerate().filter(|(_, text)| {
+            let trimmed = text.trim_start();
+            !trimmed.is_empty() && !trimmed.starts_with('#')
+        });
+        let mut parent: Option<YamlKey> = None;
+        for (line, text) in content {
+            let key = parse_key(line, text);
+            if let (Some(parent), Some(key)) = (&parent, &key) {
+                if parent.raw_value.is_empty() && key.indent > parent.indent {
+                    return key.indent - parent.indent;
+                }
+            }
+            parent = key;
+        }
+        DEFAULT_INDENT
+    }
+
+    fn insert_path(&mut self, at: usize, indent: usize, path: &[&str], raw_value: &str) {
+        let unit = self.indent_unit();
+        let new_lines: Vec<String> = path
+            .iter()
+            .enumerate()
+            .map(|(depth, key)| {
+                let prefix = " ".repeat(indent + depth * unit);
+                if depth + 1 == path.len() {
+                    format!("{}{}: {}", prefix, quote_key(key), raw_value)
+                } else {
+                    format!("{}{}:", prefix, quote_key(key))
+                }
+            })
+            .collect();
+        self.lines.splice(at..at, new_lines);
+    }
+}
+
+impl fmt::Display for YarnrcYml {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let newline = if self.crlf { "\r\n" } else { "\n" };
+        for (index, line) in self.lines.iter().enumerate() {
+            if index > 0 {
+                f.write_str(newline)?;
+            }
+            f.write_str(line)?;
+        }
+        if !self.lines.is_empty() && self.trailing_newline {
+            f.write_str(newline)?;
+        }
+        Ok(())
+    }
+}
+
+/// A `key value` line of a Yarn 1 `.yarnrc`
+#[derive(Debug, Clone, PartialEq)]
+struct YarnrcEntry {
+    key: String,
+    value: String,
+    /// Original text of the line, kept until the entry is modified
+    raw: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum YarnrcLine {
+    /// Blank lines and `#` comments
+    Other(String),
+    Entry(YarnrcEntry),
+}
+
+impl YarnrcLine {
+    fn parse(raw: &str) -> Self {
+        let trimmed = raw.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            return YarnrcLine::Other(raw.to_string());
+        }
+
+        let (raw_key, rest) = match trimmed.chars().next() {
+            Some('"') => match closing_quote(trimmed, '"') {
+                Some(close) => trimmed.split_at(close + 1),
+                None => (trimmed, ""),
+            },
+            _ => trimmed
+                .split_once(char::is_whitespace)
+                .unwrap_or((trimmed, "")),
+        };
+        let raw_key = raw_key.strip_suffix(':').unwrap_or(raw_key);
+
+        YarnrcLine::Entry(YarnrcEntry {
+            key: unquote_scalar(raw_key),
+            value: unquote_scalar(rest.trim()),
+            raw: Some(raw.to_string()),
+        })
+    }
+
+    fn render(&self) -> String {
+        match self {
+            YarnrcLine::Other(raw) => raw.clone(),
+            YarnrcLine::Entry(entry) => match &entry.raw {
+                Some(raw) => raw.clone(),
+                None => format!("{} {}", quote_key(&entry.key), quote_scalar(&entry.value)),
+            },
+        }
+    }
+}
+
+/// Yarn 1's `.yarnrc`, made of `key "value"` lines.
+///
+/// Like [`Npmrc`](super::npmrc::Npmrc), lines that are not touched through
+/// `set`/`remove` are written back unchanged.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Yarnrc {
+    lines: Vec<YarnrcLine>,
+    crlf: bool,
+    trailing_newline: bool,
+}
+
+impl Default for Yarnrc {
+    fn default() -> Self {
+        Self {
+            lines: Vec::new(),
+            crlf: false,
+            trailing_newline: true,
+        }
+    }
+}
+
+impl Yarnrc {
+    pub fn parse(content: &str) -> Self {
+        let crlf = content.contains("\r\n");
+        let trailing_newline = content.is_empty() || content.ends_with('\n');
+        let body = content.strip_suffix('\n').unwrap_or(content);
+
+        let lines = if content.is_empty() {
+            Vec::new()
+        } else {
+            body.split('\n')
+                .map(|line| YarnrcLine::parse(line.strip_suffix('\r').unwrap_or(line)))
+                .collect()
+        };
+
+        Self {
+            lines,
+            crlf,
+            trailing_newline,
+        }
+    }
+
+    /// Read a `.yarnrc` file, returning an empty document if it does not exist
+    pub async fn load(path: &Path) -> Result<Self> {
+        match fs::read_to_string(path).await {
+            Ok(content) => Ok(Self::parse(&content)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(source) => Err(Error::PackageManagerConfig {
+                path: path.to_path_buf(),
+                source,
+            }),
+        }
+    }
+
+    pub async fn save(&self, path: &Path) -> Result<()> {
+        fs::write(path, self.to_string())
+            .await
+            .map_err(|source| Error::PackageManagerConfig {
+                path: path.to_path_buf(),
+                source,
+            })
+    }
+
+    fn entries(&self) -> impl Iterator<Item = &YarnrcEntry> {
+        self.lines.iter().filter_map(|line| match line {
+            YarnrcLine::Entry(entry) => Some(entry),
+            YarnrcLine::Other(_) => None,
+        })
+    }
+
+    /// Value of a key; the last occurrence wins
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries()
+            .filter(|entry| entry.key == key)
+            .last()
+            .map(|entry| entry.value.as_str())
+    }
+
+    /// Set a key, updating the first occurrence in place and dropping any
+    /// later duplicates
+    pub fn set(&mut self, key: &str, value: &str) {
+        let mut found = false;
+        self.lines.retain_mut(|line| match line {
+            YarnrcLine::Entry(entry) if entry.key == key => {
+                if found {
+                    return false;
+                }
+                found = true;
+                entry.value = value.to_string();
+                entry.raw = None;
+                true
+            }
+            _ => true,
+        });
+
+        if !found {
+            self.lines.push(YarnrcLine::Entry(YarnrcEntry {
+                key: key.to_string(),
+                value: value.to_string(),
+                raw: None,
+            }));
+        }
+    }
+
+    /// Remove every occurrence of a key, returning whether any was present
+    pub fn remove(&mut self, key: &str) -> bool {
+        let before = self.lines.len();
+        self.lines
+            .retain(|line| !matches!(line, YarnrcLine::Entry(entry) if entry.key == key));
+        self.lines.len() != before
+    }
+}
+
+impl fmt::Display for Yarnrc {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let newline = if self.crlf { "\r\n" } else { "\n" };
+        for (index, line) in self.lines.iter().enumerate() {
+            if index > 0 {
+                f.write_str(newline)?;
+            }
+            f.write_str(&line.render())?;
+        }
+        if !self.lines.is_empty() && self.trailing_newline {
+            f.write_str(newline)?;
+        }
+        Ok(())
+    }
+}
+
+/// Path of the global (`~/.yarnrc.yml`) or local (project root) config file
+pub fn yarnrc_yml_path(is_local: bool) -> Result<PathBuf> {
+    if is_local {
+        return Ok(project_dir()?.join(".yarnrc.yml"));
+    }
+
+    dirs::home_dir()
+        .map(|home| home.join(".yarnrc.yml"))
+        .ok_or_else(|| Error::NotFound("Home directory".to_string()))
+}
+
+/// Path of the global (`~/.yarnrc`) or local (project root) Yarn 1 config file
+pub fn yarnrc_path(is_local: bool) -> Result<PathBuf> {
+    if is_local {
+        return Ok(project_dir()?.join(".yarnrc"));
+    }
+
+    dirs::home_dir()
+        .map(|home| home.join(".yarnrc"))
+        .ok_or_else(|| Error::NotFound("Home directory".to_string()))
+}
+
+/// Key under `npmRegistries` for a registry URL: Yarn matches registries
+/// without their protocol or trailing slash
+pub fn yarn_registry_key(url: &str) -> String {
+    let without_scheme = url.split_once("://").map_or(url, |(_, rest)| rest);
+    format!("//{}", without_scheme.trim_end_matches('/'))
+}
+
+fn parse_key(line: usize, text: &str) -> Option<YamlKey> {
+    let trimmed = text.trim_start();
+    if trimmed.is_empty() || trimmed.starts_with('#') || trimmed.starts_with('-') {
+        return None;
+    }
+    let indent = text.len() - trimmed.len();
+
+    let (raw_key, key, rest) = match trimmed.chars().next()? {
+        quote @ ('"' | '\'') => {
+            let close = closing_quote(trimmed, quote)?;
+            let raw_key = &trimmed[..=close];
+            let rest = trimmed[close + 1..].trim_start().strip_prefix(':')?;
+            (raw_key, unquote_scalar(raw_key), rest)
+        }
+        _ => {
+            let colon = trimmed
+                .match_indices(':')
+                .map(|(index, _)| index)
+                .find(|index| {
+                    trimmed[index + 1..]
+                        .chars()
+                        .next()
+                        .is_none_or(char::is_whitespace)
+                })?;
+            let raw_key = trimmed[..colon].trim_end();
+            (raw_key, raw_key.to_string(), &trimmed[colon + 1..])
+        }
+    };
+
+    let (raw_value, comment) = split_comment(rest.trim());
+    Some(YamlKey {
+        line,
+        indent,
+        raw_key: raw_key.to_string(),
+        key,
+        raw_value: raw_value.to_string(),
+        comment: comment.map(str::to_string),
+    })
+}
+
+/// Split `value # comment`, ignoring `#` inside quoted scalars
+fn split_comment(value: &str) -> (&str, Option<&str>) {
+    if value.starts_with('#') {
+        return ("", Some(value));
+    }
+    let search_from = match value.chars().next() {
+        Some(quote @ ('"' | '\'')) => closing_quote(value, quote).map_or(0, |close| close + 1),
+        _ => 0,
+    };
+    match value[search_from..].find(" #") {
+        Some(index) => {
+            let index = search_from + index;
+            (value[..index].trim_end(), Some(value[index + 1..].trim()))
+        }
+        None => (value, None),
+    }
+}
+
+fn closing_quote(text: &str, quote: char) -> Option<usize> {
+    let mut escaped = false;
+    for (index, c) in text.char_indices().skip(1) {
+        match c {
+            '\\' if quote == '"' && !escaped => escaped = true,
+            c if c == quote && !escaped => return Some(index),
+            _ => escaped = false,
+        }
+    }
+    None
+}
+
+fn render_comment(comment: &Option<String>) -> String {
+    comment
+        .as_ref()
+        .map(|comment| format!(" {}", comment))
+        .unwrap_or_default()
+}
+
+/// Decode a YAML scalar as written in the file
+pub fn unquote_scalar(raw: &str) -> String {
+    if raw.len() >= 2 && raw.starts_with('"') && raw.ends_with('"') {
+        let mut value = String::new();
+        let mut chars = raw[1..raw.len() - 1].chars();
+        while let Some(c) = chars.next() {
+            if c != '\\' {
+                value.push(c);
+                continue;
+            }
+            match chars.next() {
+                Some('n') => value.push('\n'),
+                Some('t') => value.push('\t'),
+                Some(other) => value.push(other),
+                None => value.push('\\'),
+            }
+        }
+        return value;
+    }
+    if raw.len() >= 2 && raw.starts_with('\'') && raw.ends_with('\'') {
+        return raw[1..raw.len() - 1].replace("''", "'");
+    }
+    raw.to_string()
+}
+
+/// Double-quoted scalar, as Yarn itself writes string settings
+pub fn quote_scalar(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn quote_key(key: &str) -> String {
+    let plain = !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'));
+    if plain {
+        key.to_string()
+    } else {
+        quote_scalar(key)
+    }
+}
@@ -0,0 +1,251 @@
+use rust_nrm::utils::catalog::{
+    export_registries, merge_registries, normalize_registries, parse_catalog,
+    parse_ini_registries, CatalogFormat, ConflictStrategy,
+};
+use rust_nrm::utils::registries::Registry;
+use std::collections::HashMap;
+
+const NRMRC: &str = "[company]\n\
+home=https://company.com\n\
+registry=https://npm.company.com/\n\
+\n\
+[npm]\n\
+registry=https://registry.npmjs.org/\n\
+\n\
+[taobao]\n\
+registry=https://registry.npm.taobao.org/\n";
+
+fn existing() -> HashMap<String, Registry> {
+    HashMap::from([
+        (
+            "npm".to_string(),
+            Registry {
+                registry: "https://registry.npmjs.org/".to_string(),
+                home: Some("https://www.npmjs.org".to_string()),
+                ..Default::default()
+            },
+        ),
+        (
+            "taobao".to_string(),
+            Registry {
+                registry: "https://registry.npmmirror.com/".to_string(),
+                home: None,
+                ..Default::default()
+            },
+        ),
+    ])
+}
+
+#[test]
+fn test_parse_nrmrc() {
+    let registries = parse_ini_registries(NRMRC).unwrap();
+    assert_eq!(registries.len(), 3);
+    assert_eq!(registries[0].0, "company");
+    assert_eq!(registries[0].1.registry, "https://npm.company.com/");
+    assert_eq!(registries[0].1.home.as_deref(), Some("https://company.com"));
+    assert_eq!(registries[1].1.home, None);
+
+    assert!(parse_ini_registries("[broken]\nhome=https://x.com\n").is_err());
+}
+
+#[test]
+fn test_merge_skip() {
+    let mut target = existing();
+    let report = merge_registries(
+        &mut target,
+        parse_ini_registries(NRMRC).unwrap(),
+        ConflictStrategy::Skip,
+    );
+    assert_eq!(report.added, vec!["company"]);
+    assert_eq!(report.unchanged, vec!["npm"]);
+    assert_eq!(report.skipped, vec!["taobao"]);
+    assert_eq!(target["taobao"].registry, "https://registry.npmmirror.com/");
+}
+
+#[test]
+fn test_merge_overwrite() {
+    let mut target = existing();
+    let report = merge_registries(
+        &mut target,
+        parse_ini_registries(NRMRC).unwrap(),
+        ConflictStrategy::Overwrite,
+    );
+    assert_eq!(report.updated, vec!["taobao"]);
+    assert_eq!(target["taobao"].registry, "https://registry.npm.taobao.org/");
+}
+
+#[test]
+fn test_merge_rename() {
+    let mut target = existing();
+    let incoming = parse_ini_registries(NRMRC).unwrap();
+    let report = merge_registries(&mut target, incoming.clone(), ConflictStrategy::Rename);
+    assert_eq!(
+        report.renamed,
+        vec![("taobao".to_string(), "taobao-1".to_string())]
+    );
+    assert_eq!(target["taobao-1"].registry, "https://registry.npm.taobao.org/");
+
+    // A second import does not create more copies
+    let report = merge_registries(&mut target, incoming, ConflictStrategy::Rename);
+    assert!(!report.has_changes());
+}
+
+fn with_metadata() -> HashMap<String, Registry> {
+    let mut registries = existing();
+    registries.insert(
+        "company.internal".to_string(),
+        Registry {
+            registry: "https://npm.company.com/".to_string(),
+            home: Some("https://company.com".to_string()),
+            proxy: Some("http://proxy.company.com:8080".to_string()),
+            extra: [
+                ("team".to_string(), toml::Value::String("web".to_string())),
+                ("priority".to_string(), toml::Value::Integer(1)),
+            ]
+            .into(),
+        },
+    );
+    registries
+}
+
+#[test]
+fn test_export_roundtrip() {
+    let registries = with_metadata();
+
+    for format in [CatalogFormat::Toml, CatalogFormat::Json] {
+        let content = export_registries(&registries, format).unwrap();
+        let parsed: HashMap<String, Registry> = parse_catalog(&content, Some(format))
+            .unwrap()
+            .into_iter()
+            .collect();
+        assert_eq!(parsed, registries, "{:?} round-trip", format);
+
+        // Detection from content gives the same result
+        let detected: HashMap<String, Registry> =
+            parse_catalog(&content, None).unwrap().into_iter().collect();
+        assert_eq!(detected, registries);
+    }
+
+    // ini stores every value as a string
+    let content = export_registries(&registries, CatalogFormat::Ini).unwrap();
+    assert!(content.starts_with("[company\\.internal]\nregistry=https://npm.company.com/\n"));
+    let parsed: HashMap<String, Registry> = parse_catalog(&content, None)
+        .unwrap()
+        .into_iter()
+        .collect();
+    let company = &parsed["company.internal"];
+    assert_eq!(company.home.as_deref(), Some("https://company.com"));
+    assert_eq!(
+        company.proxy.as_deref(),
+        Some("http://proxy.company.com:8080")
+    );
+    assert_eq!(company.extra["priority"], toml::Value::String("1".to_string()));
+    assert_eq!(parsed["npm"], registries["npm"]);
+}
+
+#[test]
+fn test_catalog_format_from_path() {
+    use std::path::Path;
+    assert_eq!(
+        CatalogFormat::from_path(Path::new("team.json")),
+        Some(CatalogFormat::Json)
+    );
+    assert_eq!(
+        CatalogFormat::from_path(Path::new("/home/me/.nrmrc")),
+        None
+    );
+    assert_eq!(
+        CatalogFormat::from_path(Path::new("registries.TOML")),
+        Some(CatalogFormat::Toml)
+    );
+}
+
+#[test]
+fn test_merge_metadata_conflict() {
+    let mut target = existing();
+    let incoming = vec![(
+        "npm".to_string(),
+        Registry {
+            registry: "https://registry.npmjs.org".to_string(),
+            home: Some("https://npmjs.com".to_string()),
+            ..Default::default()
+        },
+    )];
+    let report = merge_registries(&mut target, incoming, ConflictStrategy::Skip);
+    assert_eq!(report.conflicting, vec!["npm"]);
+    assert!(report.skipped.is_empty());
+    assert_eq!(target["npm"].home.as_deref(), Some("https://www.npmjs.org"));
+}
+
+#[test]
+fn test_normalize_registries() {
+    let registries = normalize_registries(vec![(
+        "company".to_string(),
+        Registry {
+            registry: " https://npm.company.com/npm ".to_string(),
+            home: Some("https://company.com".to_string()),
+            ..Default::default()
+        },
+    )])
+    .unwrap();
+    assert_eq!(registries[0].1.registry, "https://npm.company.com/npm/");
+
+    let invalid = |registry: Registry| normalize_registries(vec![("x".to_string(), registry)]);
+    assert!(invalid(Registry {
+        registry: "ftp://npm.company.com/".to_string(),
+        ..Default::default()
+    })
+    .is_err());
+    assert!(invalid(Registry {
+        registry: "https://npm.company.com/".to_string(),
+        home: Some("company.com".to_string()),
+        ..Default::default()
+    })
+    .is_err());
+    assert!(invalid(Registry {
+        registry: "https://npm.company.com/".to_string(),
+        proxy: Some("socks5://proxy.company.com:1080".to_string()),
+        ..Default::default()
+    })
+    .is_err());
+}
+
+#[test]
+fn test_merge_proxy_change() {
+    let incoming = |proxy: &str| {
+        vec![(
+            "company.internal".to_string(),
+            Registry {
+                registry: "https://npm.company.com/".to_string(),
+                proxy: Some(proxy.to_string()),
+                ..Default::default()
+            },
+        )]
+    };
+
+    let mut target = with_metadata();
+    let report = merge_registries(
+        &mut target,
+        incoming("http://proxy.company.com:8080"),
+        ConflictStrategy::Skip,
+    );
+    assert_eq!(report.unchanged, vec!["company.internal"]);
+
+    let report = merge_registries(
+        &mut target,
+        incoming("http://other-proxy:3128"),
+        ConflictStrategy::Skip,
+    );
+    assert_eq!(report.conflicting, vec!["company.internal"]);
+
+    let report = merge_registries(
+        &mut target,
+        incoming("http://other-proxy:3128"),
+        ConflictStrategy::Overwrite,
+    );
+    assert_eq!(report.updated, vec!["company.internal"]);
+    assert_eq!(
+        target["company.internal"].proxy.as_deref(),
+        Some("http://other-proxy:3128")
+    );
+}
@@ -0,0 +1,119 @@
+use clap::Parser;
+use rust_nrm::utils::cli::{Cli, Commands};
+use rust_nrm::utils::history::{
+    append_history, load_history, parse_age, summarize, HistoryEntry,
+};
+use rust_nrm::utils::error::Error;
+use rust_nrm::utils::speed_test::{FailureKind, SpeedTestResult, TestFailure};
+use std::time::Duration;
+use tempfile::TempDir;
+
+fn entry(timestamp: u64, name: &str, response_time_ms: Option<f64>) -> HistoryEntry {
+    HistoryEntry {
+        timestamp,
+        name: name.to_string(),
+        url: format!("https://{}.example/", name),
+        success: response_time_ms.is_some(),
+        response_time_ms,
+        error_kind: response_time_ms.is_none().then_some(FailureKind::Timeout),
+    }
+}
+
+#[test]
+fn test_parse_age() {
+    assert_eq!(parse_age("90m"), Ok(Duration::from_secs(90 * 60)));
+    assert_eq!(parse_age("12h"), Ok(Duration::from_secs(12 * 3600)));
+    assert_eq!(parse_age("7d"), Ok(Duration::from_secs(7 * 86400)));
+    assert_eq!(parse_age("2w"), Ok(Duration::from_secs(14 * 86400)));
+    assert!(parse_age("d").is_err());
+    assert!(parse_age("7y").is_err());
+
+    let cli = Cli::try_parse_from(["rust-nrm", "history", "--registry", "npm", "--since", "7d"])
+        .unwrap();
+    assert!(matches!(
+        cli.command,
+        Commands::History { registry: Some(ref name), since: Some(since) }
+            if name == "npm" && since == Duration::from_secs(7 * 86400)
+    ));
+    assert!(Cli::try_parse_from(["rust-nrm", "history", "--since", "soon"]).is_err());
+}
+
+#[test]
+fn test_summarize() {
+    let entries = vec![
+        entry(4, "npm", Some(300.0)),
+        entry(1, "npm", Some(100.0)),
+        entry(2, "npm", None),
+        entry(3, "npm", Some(200.0)),
+        entry(1, "taobao", Some(50.0)),
+    ];
+    let trends = summarize(&entries);
+    assert_eq!(trends.len(), 2);
+
+    let npm = &trends[0];
+    assert_eq!(npm.name, "npm");
+    assert_eq!(npm.runs, 4);
+    assert_eq!(npm.failures, 1);
+    assert_eq!(npm.failure_rate(), 0.25);
+    assert_eq!(npm.average_ms, Some(200.0));
+    // Older half averages 100ms, newer half 250ms
+    assert_eq!(npm.change, Some(1.5));
+    assert_eq!(npm.describe_change(), "+150% slower");
+    assert_eq!(npm.sparkline, "▁×▅█");
+
+    let taobao = &trends[1];
+    assert_eq!(taobao.change, None);
+    assert_eq!(taobao.describe_change(), "-");
+    assert_eq!(taobao.sparkline, "▁");
+}
+
+#[tokio::test]
+async fn test_append_and_load_history() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("nested").join("history.jsonl");
+    assert_eq!(load_history(&path).await.unwrap(), vec![]);
+
+    let ok = SpeedTestResult {
+        name: "npm".to_string(),
+        url: "https://registry.npmjs.org/".to_string(),
+        response_time: 0.25,
+        is_success: true,
+        ..Default::default()
+    };
+    let failed = SpeedTestResult {
+        name: "taobao".to_string(),
+        url: "https://registry.npmmirror.com/".to_string(),
+        response_time: 5.0,
+        failure: Some(TestFailure {
+            kind: FailureKind::Dns,
+            status: None,
+            message: "DNS: no address".to_string(),
+        }),
+        ..Default::default()
+    };
+    let first = vec![
+        HistoryEntry::from_result(&ok, 100),
+        HistoryEntry::from_result(&failed, 100),
+    ];
+    assert_eq!(first[0].response_time_ms, Some(250.0));
+    assert_eq!(first[1].response_time_ms, None);
+    assert_eq!(first[1].error_kind, Some(FailureKind::Dns));
+
+    append_history(&path, &first).await.unwrap();
+    // A line cut short by a crash does not hide the rest
+    let mut content = std::fs::read_to_string(&path).unwrap();
+    content.push_str("{\"timestamp\": 1\n");
+    std::fs::write(&path, content).unwrap();
+    let second = vec![HistoryEntry::from_result(&ok, 200)];
+    append_history(&path, &second).await.unwrap();
+
+    let loaded = load_history(&path).await.unwrap();
+    assert_eq!(loaded.len(), 3);
+    assert_eq!(loaded[..2], first[..]);
+    assert_eq!(loaded[2], second[0]);
+
+    // A directory in place of the file is an I/O error, not a config one
+    let err = load_history(dir.path()).await.unwrap_err();
+    assert!(matches!(err, Error::Io { .. }));
+    assert_eq!(err.exit_code(), 10);
+}
@@ -0,0 +1,98 @@
+use clap::Parser;
+use rust_nrm::utils::cli::Cli;
+use rust_nrm::utils::marker::{find_marker, parse_marker, pin_marker, ProjectMarker};
+use std::path::Path;
+use tempfile::TempDir;
+
+#[tokio::test]
+async fn test_find_marker_walks_up() {
+    let dir = TempDir::new().unwrap();
+    let nested = dir.path().join("packages").join("app");
+    std::fs::create_dir_all(&nested).unwrap();
+    assert_eq!(find_marker(&nested).await.unwrap(), None);
+
+    std::fs::write(dir.path().join(".nrmrc"), "# team registry\ntaobao\n").unwrap();
+    let found = find_marker(&nested).await.unwrap().unwrap();
+    assert_eq!(found.dir(), dir.path());
+    assert_eq!(found.marker.registry.as_deref(), Some("taobao"));
+
+    // rust-nrm.toml wins over .nrmrc in the same directory
+    std::fs::write(
+        dir.path().join("rust-nrm.toml"),
+        "registry = \"npm\"\n\n[scopes]\n\"@company\" = \"https://npm.company.com/\"\n",
+    )
+    .unwrap();
+    let found = find_marker(&nested).await.unwrap().unwrap();
+    assert_eq!(found.path, dir.path().join("rust-nrm.toml"));
+    assert_eq!(found.marker.registry.as_deref(), Some("npm"));
+    assert_eq!(
+        found.marker.scopes.get("@company").map(String::as_str),
+        Some("https://npm.company.com/")
+    );
+}
+
+#[test]
+fn test_nrm_registry_list_is_not_a_marker() {
+    let path = Path::new("/home/user/.nrmrc");
+    let nrm_config = "[company]\nregistry=https://npm.company.com/\n";
+    assert_eq!(parse_marker(path, nrm_config).unwrap(), None);
+    assert_eq!(parse_marker(path, "\n# nothing\n").unwrap(), None);
+    assert_eq!(
+        parse_marker(path, "yarn\n").unwrap(),
+        Some(ProjectMarker {
+            registry: Some("yarn".to_string()),
+            ..Default::default()
+        })
+    );
+    assert!(parse_marker(Path::new("rust-nrm.toml"), "registry = [").is_err());
+}
+
+#[tokio::test]
+async fn test_pin_marker() {
+    let dir = TempDir::new().unwrap();
+    let path = pin_marker(dir.path(), "taobao").await.unwrap();
+    assert_eq!(path, dir.path().join("rust-nrm.toml"));
+    assert_eq!(
+        std::fs::read_to_string(&path).unwrap(),
+        "registry = \"taobao\"\n"
+    );
+
+    // Re-pinning keeps scopes and comments
+    std::fs::write(
+        &path,
+        "# shared with the team\nregistry = \"taobao\"\n\n[scopes]\n\"@company\" = \"github\"\n",
+    )
+    .unwrap();
+    pin_marker(dir.path(), "npm").await.unwrap();
+    assert_eq!(
+        std::fs::read_to_string(&path).unwrap(),
+        "# shared with the team\nregistry = \"npm\"\n\n[scopes]\n\"@company\" = \"github\"\n"
+    );
+
+    // An existing .nrmrc is updated instead of adding a second marker
+    let other = TempDir::new().unwrap();
+    std::fs::write(other.path().join(".nrmrc"), "taobao\n").unwrap();
+    let path = pin_marker(other.path(), "npm").await.unwrap();
+    assert_eq!(path, other.path().join(".nrmrc"));
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "npm\n");
+
+    // Only the registry line of a .nrmrc changes
+    std::fs::write(
+        &path,
+        "# team registry, see CONTRIBUTING.md\n\ntaobao\n# mirror for CI\n",
+    )
+    .unwrap();
+    pin_marker(other.path(), "npm").await.unwrap();
+    assert_eq!(
+        std::fs::read_to_string(&path).unwrap(),
+        "# team registry, see CONTRIBUTING.md\n\nnpm\n# mirror for CI\n"
+    );
+}
+
+#[test]
+fn test_use_requires_registry_or_auto() {
+    assert!(Cli::try_parse_from(["rust-nrm", "use"]).is_err());
+    assert!(Cli::try_parse_from(["rust-nrm", "use", "--auto"]).is_ok());
+    assert!(Cli::try_parse_from(["rust-nrm", "use", "npm", "--auto"]).is_err());
+    assert!(Cli::try_parse_from(["rust-nrm", "use", "--auto", "--local"]).is_err());
+}
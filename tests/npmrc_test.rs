@@ -0,0 +1,136 @@
+use rust_nrm::utils::npmrc::{
+    expand_env, normalize_scope, scope_registry_key, Npmrc, NpmrcLine,
+};
+
+const SAMPLE: &str = "# company settings\n\
+registry=https://registry.npmjs.org/\n\
+\n\
+@company:registry=https://npm.company.com/\n\
+//npm.company.com/:_authToken=${COMPANY_TOKEN}\n\
+; legacy comment\n\
+ca[]=\"first cert\"\n\
+ca[]=second\n\
+save-exact = true\n";
+
+#[test]
+fn test_parse_roundtrip_is_lossless() {
+    let npmrc = Npmrc::parse(SAMPLE);
+    assert_eq!(npmrc.to_string(), SAMPLE);
+
+    let crlf = "registry=https://a.com/\r\n# note\r\n";
+    assert_eq!(Npmrc::parse(crlf).to_string(), crlf);
+
+    let no_newline = "registry=https://a.com/";
+    assert_eq!(Npmrc::parse(no_newline).to_string(), no_newline);
+}
+
+#[test]
+fn test_parse_lines() {
+    let npmrc = Npmrc::parse(SAMPLE);
+    let lines = npmrc.lines();
+    assert!(matches!(lines[0], NpmrcLine::Comment(_)));
+    assert!(matches!(lines[2], NpmrcLine::Blank(_)));
+    assert!(matches!(lines[5], NpmrcLine::Comment(_)));
+
+    assert_eq!(npmrc.get("registry"), Some("https://registry.npmjs.org/"));
+    assert_eq!(
+        npmrc.get("@company:registry"),
+        Some("https://npm.company.com/")
+    );
+    assert_eq!(npmrc.get("save-exact"), Some("true"));
+    assert_eq!(npmrc.get_all("ca"), vec!["first cert", "second"]);
+    assert_eq!(npmrc.get("ca"), None);
+}
+
+#[test]
+fn test_set_preserves_other_lines() {
+    let mut npmrc = Npmrc::parse(SAMPLE);
+    npmrc.set("registry", "https://registry.npmmirror.com/");
+
+    let expected = SAMPLE.replace(
+        "registry=https://registry.npmjs.org/",
+        "registry=https://registry.npmmirror.com/",
+    );
+    assert_eq!(npmrc.to_string(), expected);
+}
+
+#[test]
+fn test_set_appends_and_collapses_duplicates() {
+    let mut npmrc = Npmrc::parse("# only a comment\n");
+    npmrc.set("registry", "https://a.com/");
+    assert_eq!(npmrc.to_string(), "# only a comment\nregistry=https://a.com/\n");
+
+    let mut npmrc = Npmrc::parse("registry=https://a.com/\nfoo=bar\nregistry=https://b.com/\n");
+    npmrc.set("registry", "https://c.com/");
+    assert_eq!(npmrc.to_string(), "registry=https://c.com/\nfoo=bar\n");
+
+    let mut npmrc = Npmrc::default();
+    npmrc.set("registry", "https://a.com/");
+    assert_eq!(npmrc.to_string(), "registry=https://a.com/\n");
+}
+
+#[test]
+fn test_remove_and_arrays() {
+    let mut npmrc = Npmrc::parse(SAMPLE);
+    assert!(npmrc.remove("@company:registry"));
+    assert!(!npmrc.remove("missing"));
+    assert!(!npmrc.contains_key("@company:registry"));
+
+    npmrc.set_all("ca", &["only"]);
+    assert_eq!(npmrc.get_all("ca"), vec!["only"]);
+    assert!(npmrc.to_string().contains("; legacy comment\nca[]=only\nsave-exact"));
+}
+
+#[test]
+fn test_quoting_and_comments() {
+    let npmrc = Npmrc::parse("a=\"x;y\"\nb=plain ; trailing\nc='single'\nd=esc\\;aped\nflag\n");
+    assert_eq!(npmrc.get("a"), Some("x;y"));
+    assert_eq!(npmrc.get("b"), Some("plain"));
+    assert_eq!(npmrc.get("c"), Some("single"));
+    assert_eq!(npmrc.get("d"), Some("esc;aped"));
+    assert_eq!(npmrc.get("flag"), Some("true"));
+
+    let mut npmrc = Npmrc::default();
+    npmrc.set("a", "x;y");
+    npmrc.set("b", " padded ");
+    let rendered = npmrc.to_string();
+    assert_eq!(rendered, "a=x\\;y\nb=\" padded \"\n");
+    let reparsed = Npmrc::parse(&rendered);
+    assert_eq!(reparsed.get("a"), Some("x;y"));
+    assert_eq!(reparsed.get("b"), Some(" padded "));
+}
+
+#[test]
+fn test_env_expansion() {
+    std::env::set_var("RUST_NRM_NPMRC_TEST_TOKEN", "secret");
+    assert_eq!(
+        expand_env("${RUST_NRM_NPMRC_TEST_TOKEN}-${RUST_NRM_NPMRC_TEST_MISSING}"),
+        "secret-${RUST_NRM_NPMRC_TEST_MISSING}"
+    );
+    assert_eq!(expand_env("\\${RUST_NRM_NPMRC_TEST_TOKEN}"), "${RUST_NRM_NPMRC_TEST_TOKEN}");
+
+    let npmrc = Npmrc::parse("//host/:_authToken=${RUST_NRM_NPMRC_TEST_TOKEN}\n");
+    assert_eq!(npmrc.get("//host/:_authToken"), Some("${RUST_NRM_NPMRC_TEST_TOKEN}"));
+    assert_eq!(
+        npmrc.get_expanded("//host/:_authToken"),
+        Some("secret".to_string())
+    );
+}
+
+#[test]
+fn test_scopes() {
+    assert_eq!(normalize_scope("company"), Some("@company".to_string()));
+    assert_eq!(normalize_scope("@company"), Some("@company".to_string()));
+    assert_eq!(normalize_scope("@"), None);
+    assert_eq!(normalize_scope("@a/b"), None);
+    assert_eq!(scope_registry_key("@company"), "@company:registry");
+
+    let npmrc = Npmrc::parse(SAMPLE);
+    assert_eq!(
+        npmrc.scopes(),
+        vec![(
+            "@company".to_string(),
+            "https://npm.company.com/".to_string()
+        )]
+    );
+}
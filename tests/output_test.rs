@@ -0,0 +1,95 @@
+use rust_nrm::utils::output::{OutputFormat, RegistryRecord};
+use rust_nrm::utils::speed_test::{rank_results, FailureKind, SpeedTestResult, TestFailure};
+
+#[test]
+fn test_record_schema_is_stable() {
+    let record = RegistryRecord {
+        name: Some("npm".to_string()),
+        url: "https://registry.npmjs.org/".to_string(),
+        ..Default::default()
+    };
+
+    // Fields are emitted in declaration order
+    let json = serde_json::to_string(&record).unwrap();
+    let keys = [
+        "name",
+        "url",
+        "home",
+        "active_global",
+        "active_local",
+        "active_yarn",
+        "response_time_ms",
+        "success",
+        "ttfb_ms",
+        "throughput_mbps",
+        "status",
+        "error_kind",
+        "error",
+        "dns_ms",
+        "connect_ms",
+        "tls_ms",
+        "body_ms",
+        "rank",
+    ];
+    let positions: Vec<usize> = keys
+        .iter()
+        .map(|key| json.find(&format!("\"{}\":", key)).unwrap())
+        .collect();
+    assert!(positions.windows(2).all(|pair| pair[0] < pair[1]));
+
+    let value = serde_json::to_value(&record).unwrap();
+    let object = value.as_object().unwrap();
+    assert_eq!(object.len(), keys.len());
+    assert!(object["home"].is_null());
+    assert!(object["response_time_ms"].is_null());
+    assert_eq!(object["active_global"], false);
+    assert!(object["rank"].is_null());
+}
+
+#[test]
+fn test_test_records_keep_ranking() {
+    let result = |name: &str, response_time: f64, is_success: bool| SpeedTestResult {
+        name: name.to_string(),
+        url: format!("https://{}.example.com/", name),
+        response_time,
+        is_success,
+        failure: (!is_success).then(|| TestFailure {
+            kind: FailureKind::Timeout,
+            status: None,
+            message: "timed out".to_string(),
+        }),
+        ..Default::default()
+    };
+    let mut results = vec![
+        result("down", 0.01, false),
+        result("zeta", 0.1, true),
+        result("alpha", 0.9, true),
+    ];
+    rank_results(&mut results);
+
+    let records: Vec<RegistryRecord> = results
+        .into_iter()
+        .enumerate()
+        .map(|(index, result)| RegistryRecord::from_test_result(index + 1, result))
+        .collect();
+    let ranked: Vec<(&str, Option<usize>)> = records
+        .iter()
+        .map(|record| (record.name.as_deref().unwrap(), record.rank))
+        .collect();
+    assert_eq!(
+        ranked,
+        [("zeta", Some(1)), ("alpha", Some(2)), ("down", Some(3))]
+    );
+    assert_eq!(records[0].response_time_ms, Some(100.0));
+    assert_eq!(records[0].error, None);
+    assert_eq!(records[2].success, Some(false));
+    assert_eq!(records[2].error_kind, Some(FailureKind::Timeout));
+}
+
+#[test]
+fn test_output_format() {
+    assert!(!OutputFormat::Text.is_machine());
+    assert!(OutputFormat::Json.is_machine());
+    assert!(OutputFormat::Tsv.is_machine());
+    assert_eq!(OutputFormat::default(), OutputFormat::Text);
+}
@@ -0,0 +1,151 @@
+use rust_nrm::utils::cli::{CommandExecutor, Commands, TestArgs};
+use rust_nrm::utils::npmrc::{npmrc_path, Npmrc};
+use rust_nrm::utils::registries::Store;
+use rust_nrm::utils::resolve::{effective_layer, registry_layers, RegistrySource};
+use std::path::Path;
+use tempfile::TempDir;
+use tokio::sync::Mutex;
+
+// These tests change the working directory of the whole binary, so they
+// live in their own test binary and take turns
+static CWD: Mutex<()> = Mutex::const_new(());
+
+async fn setup(dir: &Path) -> CommandExecutor {
+    std::env::set_var("NPM_CONFIG_USERCONFIG", dir.join("user.npmrc"));
+    let store = Store::load_from(&dir.join("registries.toml")).await.unwrap();
+    CommandExecutor::new(store)
+}
+
+fn use_registry(registry: &str, local: bool) -> Commands {
+    Commands::Use {
+        registry: Some(registry.to_string()),
+        local,
+        pm: None,
+        auto: false,
+        fastest: false,
+        among: vec![],
+        test: TestArgs::default(),
+    }
+}
+
+#[tokio::test]
+async fn test_local_use_from_subdirectory() {
+    let _cwd = CWD.lock().await;
+    let dir = TempDir::new().unwrap();
+    let root = dir.path().join("project");
+    let sub = root.join("packages").join("app");
+    std::fs::create_dir_all(&sub).unwrap();
+    std::fs::write(root.join("package.json"), "{}").unwrap();
+    std::env::set_current_dir(&sub).unwrap();
+
+    let mut executor = setup(dir.path()).await;
+    executor.execute(use_registry("taobao", true)).await.unwrap();
+    executor
+        .execute(Commands::SetScope {
+            scope: "company".to_string(),
+            registry: "npm".to_string(),
+            local: true,
+            pm: None,
+        })
+        .await
+        .unwrap();
+
+    // Written to the project root, where npm reads it
+    assert!(!sub.join(".npmrc").exists());
+    let npmrc = Npmrc::load(&root.join(".npmrc")).await.unwrap();
+    assert_eq!(npmrc.get("registry"), Some("https://registry.npmmirror.com/"));
+    assert_eq!(
+        npmrc.get("@company:registry"),
+        Some("https://registry.npmjs.org/")
+    );
+
+    // `ls` and `current` agree on it
+    assert_eq!(
+        npmrc_path(true).unwrap().canonicalize().unwrap(),
+        root.join(".npmrc").canonicalize().unwrap()
+    );
+    let store = Store::load_from(&dir.path().join("registries.toml"))
+        .await
+        .unwrap();
+    assert_eq!(
+        store.get_current_registry(true).await.unwrap().as_deref(),
+        Some("taobao")
+    );
+    let layers = registry_layers(&sub).await.unwrap();
+    let layer = effective_layer(&layers).unwrap();
+    assert!(matches!(layer.source, RegistrySource::Project(_)));
+    assert_eq!(
+        layer.url.as_deref(),
+        Some("https://registry.npmmirror.com/")
+    );
+
+    std::env::set_current_dir(dir.path()).unwrap();
+}
+
+#[tokio::test]
+async fn test_global_use_in_pnpm_project() {
+    let _cwd = CWD.lock().await;
+    let dir = TempDir::new().unwrap();
+    let project = dir.path().join("project");
+    std::fs::create_dir_all(&project).unwrap();
+    std::fs::write(project.join("pnpm-lock.yaml"), "lockfileVersion: '9.0'\n").unwrap();
+    std::env::set_current_dir(&project).unwrap();
+
+    let mut executor = setup(dir.path()).await;
+    executor.execute(use_registry("taobao", false)).await.unwrap();
+
+    let npmrc = Npmrc::load(&dir.path().join("user.npmrc")).await.unwrap();
+    assert_eq!(npmrc.get("registry"), Some("https://registry.npmmirror.com/"));
+    assert!(!project.join(".npmrc").exists());
+
+    std::env::set_current_dir(dir.path()).unwrap();
+}
+
+#[tokio::test]
+async fn test_current_resolves_unnormalized_urls() {
+    let _cwd = CWD.lock().await;
+    let dir = TempDir::new().unwrap();
+    let project = dir.path().join("project");
+    std::fs::create_dir_all(&project).unwrap();
+    std::fs::write(project.join("package.json"), "{}").unwrap();
+    std::env::set_current_dir(&project).unwrap();
+
+    // Written by hand, without the trailing slash
+    std::fs::write(
+        dir.path().join("user.npmrc"),
+        "registry=https://registry.npmmirror.com\n",
+    )
+    .unwrap();
+    setup(dir.path()).await;
+    let store = Store::load_from(&dir.path().join("registries.toml"))
+        .await
+        .unwrap();
+
+    let report = store.current_report(&project).await.unwrap();
+    assert_eq!(report.record.name.as_deref(), Some("taobao"));
+    assert_eq!(report.record.url, "https://registry.npmmirror.com");
+    assert!(report.record.active_global);
+    assert!(!report.record.active_local);
+    assert!(matches!(report.source, RegistrySource::User(_)));
+
+    // The project `.npmrc` wins over the user one
+    std::fs::write(
+        project.join(".npmrc"),
+        "registry=https://registry.npmjs.org\n",
+    )
+    .unwrap();
+    let report = store.current_report(&project).await.unwrap();
+    assert_eq!(report.record.name.as_deref(), Some("npm"));
+    assert!(!report.record.active_global);
+    assert!(report.record.active_local);
+    assert!(matches!(report.source, RegistrySource::Project(_)));
+
+    let value = serde_json::to_value(&report).unwrap();
+    assert_eq!(value["name"], "npm");
+    assert_eq!(value["home"], "https://www.npmjs.org");
+    assert_eq!(value["active_local"], true);
+    assert_eq!(value["source"]["layer"], "project");
+    assert_eq!(value["layers"].as_array().unwrap().len(), report.layers.len());
+
+    std::env::set_current_dir(dir.path()).unwrap();
+}
@@ -1,26 +1,22 @@
-use rust_nrm::utils::registries::{Store, Registry};
-use tokio::fs;
+use rust_nrm::utils::registries::{
+    get_config_path, normalize_registry_url, same_registry_url, Registry, Store,
+};
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
 
-async fn setup() -> Store {
-    let store = Store::load().await;
-    store
+fn config_file(dir: &TempDir) -> PathBuf {
+    dir.path().join("rust-nrm").join("registries.toml")
 }
 
-async fn cleanup() {
-    let config_path = dirs::home_dir()
-        .expect("Could not find home directory")
-        .join(".config")
-        .join("rust-nrm")
-        .join("registries.toml");
-    if config_path.exists() {
-        let _ = fs::remove_file(config_path).await;
-    }
+async fn setup(dir: &TempDir) -> Store {
+    Store::load_from(&config_file(dir)).await.unwrap()
 }
 
 #[tokio::test]
 async fn test_load_default_config() {
-    cleanup().await;
-    let store = Store::load().await;
+    let dir = TempDir::new().unwrap();
+    let store = setup(&dir).await;
+    assert!(config_file(&dir).exists(), "Default config should be created");
     
     // Verify default registries exist
     assert!(store.registries.contains_key("npm"));
@@ -31,13 +27,12 @@ async fn test_load_default_config() {
     // Verify npm registry URL
     let npm_registry = store.registries.get("npm").unwrap();
     assert_eq!(npm_registry.registry, "https://registry.npmjs.org/");
-    
-    cleanup().await;
 }
 
 #[tokio::test]
 async fn test_add_registry() {
-    let mut store = setup().await;
+    let dir = TempDir::new().unwrap();
+    let mut store = setup(&dir).await;
     
     // Add a new registry
     let name = "test-registry";
@@ -47,62 +42,133 @@ async fn test_add_registry() {
     store.registries.insert(name.to_string(), Registry {
         registry: url.to_string(),
         home,
+        ..Default::default()
     });
 
     // Verify the registry was added
     let added_registry = store.registries.get(name).unwrap();
     assert_eq!(added_registry.registry, url);
     assert_eq!(added_registry.home, Some("https://test.com".to_string()));
-    
-    cleanup().await;
 }
 
 #[tokio::test]
 async fn test_remove_registry() {
-    let mut store = setup().await;
+    let dir = TempDir::new().unwrap();
+    let mut store = setup(&dir).await;
     
     // Add and then remove a registry
     let name = "test-registry";
     store.registries.insert(name.to_string(), Registry {
         registry: "https://test.com".to_string(),
         home: None,
+        ..Default::default()
     });
     
     assert!(store.registries.contains_key(name));
     store.registries.remove(name);
     assert!(!store.registries.contains_key(name));
-    
-    cleanup().await;
 }
 
 #[tokio::test]
 async fn test_save_and_load() {
-    cleanup().await;  // Start with a clean state
-    let mut store = setup().await;
+    let dir = TempDir::new().unwrap();
+    let mut store = setup(&dir).await;
     
     // Add a test registry
     store.registries.insert("test".to_string(), Registry {
         registry: "https://test.com".to_string(),
         home: Some("https://test.com".to_string()),
+        ..Default::default()
     });
     
     // Save the store
-    store.save().await;
+    store.save().await.unwrap();
     
     // Ensure the file exists before trying to load it
-    let config_path = dirs::home_dir()
-        .expect("Could not find home directory")
-        .join(".config")
-        .join("rust-nrm")
-        .join("registries.toml");
+    let config_path = config_file(&dir);
     assert!(config_path.exists(), "Config file should exist after save");
     
     // Load a new store and verify the data
-    let loaded_store = Store::load().await;
+    let loaded_store = Store::load_from(&config_path).await.unwrap();
     assert!(loaded_store.registries.contains_key("test"), "Test registry should exist in loaded store");
     let test_registry = loaded_store.registries.get("test").unwrap();
     assert_eq!(test_registry.registry, "https://test.com", "Registry URL should match");
     assert_eq!(test_registry.home, Some("https://test.com".to_string()), "Home URL should match");
-    
-    cleanup().await;
+}
+
+#[test]
+fn test_config_path_resolution() {
+    let explicit = Path::new("/tmp/custom.toml");
+    assert_eq!(get_config_path(Some(explicit)).unwrap(), explicit);
+
+    // Environment-based resolution is covered in one test to avoid races
+    std::env::set_var("RUST_NRM_CONFIG", "/tmp/from-env.toml");
+    assert_eq!(
+        get_config_path(None).unwrap(),
+        PathBuf::from("/tmp/from-env.toml")
+    );
+
+    std::env::remove_var("RUST_NRM_CONFIG");
+    std::env::set_var("XDG_CONFIG_HOME", "/tmp/xdg");
+    assert_eq!(
+        get_config_path(None).unwrap(),
+        PathBuf::from("/tmp/xdg/rust-nrm/registries.toml")
+    );
+
+    std::env::set_var("XDG_CONFIG_HOME", "relative/dir");
+    assert!(get_config_path(None)
+        .unwrap()
+        .ends_with(".config/rust-nrm/registries.toml"));
+    std::env::remove_var("XDG_CONFIG_HOME");
+}
+
+#[test]
+fn test_normalize_registry_url() {
+    assert_eq!(
+        normalize_registry_url("https://registry.example.com").unwrap(),
+        "https://registry.example.com/"
+    );
+    assert_eq!(
+        normalize_registry_url("http://localhost:4873/npm").unwrap(),
+        "http://localhost:4873/npm/"
+    );
+    assert!(normalize_registry_url("ftp://registry.example.com").is_err());
+    assert!(normalize_registry_url("registry.example.com").is_err());
+    assert!(normalize_registry_url("https://").is_err());
+}
+
+#[tokio::test]
+async fn test_same_registry_url() {
+    assert!(same_registry_url(
+        "https://registry.npmjs.org",
+        "https://registry.npmjs.org/"
+    ));
+    assert!(same_registry_url(
+        "HTTPS://Registry.npmjs.org/",
+        "https://registry.npmjs.org/"
+    ));
+    assert!(!same_registry_url(
+        "https://registry.npmjs.org/",
+        "https://registry.npmmirror.com/"
+    ));
+
+    let dir = TempDir::new().unwrap();
+    let store = setup(&dir).await;
+    assert_eq!(
+        store.find_registry_name("https://registry.npmjs.org").map(String::as_str),
+        Some("npm")
+    );
+}
+
+#[tokio::test]
+async fn test_builtin_registries() {
+    let dir = TempDir::new().unwrap();
+    let store = setup(&dir).await;
+
+    assert!(Store::is_builtin("npm"));
+    assert!(!Store::is_builtin("test-registry"));
+    assert_eq!(
+        store.find_registry_name("https://registry.npmjs.org"),
+        Some(&"npm".to_string())
+    );
 }
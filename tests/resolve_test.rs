@@ -0,0 +1,65 @@
+use rust_nrm::utils::resolve::{
+    effective_layer, find_project_root, registry_layers, RegistrySource, DEFAULT_REGISTRY,
+};
+use std::fs;
+use tempfile::TempDir;
+
+#[test]
+fn test_find_project_root() {
+    let dir = TempDir::new().unwrap();
+    let root = dir.path().join("project");
+    let nested = root.join("src").join("deep");
+    fs::create_dir_all(&nested).unwrap();
+
+    // Without a package.json the cwd itself is the project root
+    assert_eq!(find_project_root(&nested), nested);
+
+    fs::write(root.join("package.json"), "{}").unwrap();
+    assert_eq!(find_project_root(&nested), root);
+}
+
+#[tokio::test]
+async fn test_registry_layers_precedence() {
+    let dir = TempDir::new().unwrap();
+    let project = dir.path().join("project");
+    let nested = project.join("packages").join("app");
+    fs::create_dir_all(&nested).unwrap();
+    fs::write(project.join("package.json"), "{}").unwrap();
+
+    let user_npmrc = dir.path().join("user.npmrc");
+    let global_npmrc = dir.path().join("global.npmrc");
+    fs::write(&user_npmrc, "registry=https://user.example.com/\n").unwrap();
+    fs::write(&global_npmrc, "registry=https://global.example.com/\n").unwrap();
+    std::env::set_var("NPM_CONFIG_USERCONFIG", &user_npmrc);
+    std::env::set_var("npm_config_globalconfig", &global_npmrc);
+    std::env::remove_var("npm_config_registry");
+
+    // User config beats global config
+    let layers = registry_layers(&nested).await.unwrap();
+    let winner = effective_layer(&layers).unwrap();
+    assert_eq!(winner.source, RegistrySource::User(user_npmrc.clone()));
+    assert_eq!(winner.url.as_deref(), Some("https://user.example.com/"));
+    assert_eq!(layers.last().unwrap().source, RegistrySource::Default);
+    assert_eq!(layers.last().unwrap().url.as_deref(), Some(DEFAULT_REGISTRY));
+
+    // Project config (found by walking up) beats user config
+    fs::write(
+        project.join(".npmrc"),
+        "registry=https://project.example.com/\n",
+    )
+    .unwrap();
+    let layers = registry_layers(&nested).await.unwrap();
+    let winner = effective_layer(&layers).unwrap();
+    assert_eq!(
+        winner.source,
+        RegistrySource::Project(project.join(".npmrc"))
+    );
+
+    // The environment beats everything
+    std::env::set_var("NPM_CONFIG_REGISTRY", "https://env.example.com/");
+    let layers = registry_layers(&nested).await.unwrap();
+    let winner = effective_layer(&layers).unwrap();
+    assert_eq!(winner.source, RegistrySource::Env);
+    assert_eq!(winner.url.as_deref(), Some("https://env.example.com/"));
+    std::env::remove_var("NPM_CONFIG_REGISTRY");
+}
@@ -0,0 +1,101 @@
+use clap::{CommandFactory, Parser};
+use clap_complete::engine::complete;
+use clap_complete::Shell as CompletionShell;
+use rust_nrm::utils::cli::{completion_script, Cli, Commands};
+use rust_nrm::utils::shell::Shell;
+use std::ffi::OsString;
+use tempfile::TempDir;
+
+const URL: &str = "https://registry.npmmirror.com/";
+
+#[test]
+fn test_env_script() {
+    assert_eq!(
+        Shell::Bash.env_script(URL),
+        "export npm_config_registry='https://registry.npmmirror.com/'\n\
+export YARN_NPM_REGISTRY_SERVER='https://registry.npmmirror.com/'\n\
+export BUN_CONFIG_REGISTRY='https://registry.npmmirror.com/'\n"
+    );
+    assert!(Shell::Fish
+        .env_script(URL)
+        .starts_with("set -gx npm_config_registry 'https://registry.npmmirror.com/'\n"));
+    assert!(Shell::Powershell
+        .env_script("https://it's.example.com/")
+        .starts_with("$env:npm_config_registry = 'https://it''s.example.com/'\n"));
+}
+
+#[test]
+fn test_auto_env_script() {
+    // Inside a marked project the exported names are recorded
+    assert!(Shell::Bash.auto_env_script(Some(URL), None).ends_with(
+        "export RUST_NRM_ENV='npm_config_registry,YARN_NPM_REGISTRY_SERVER,BUN_CONFIG_REGISTRY'\n"
+    ));
+
+    // Outside of it only the recorded variables are unset
+    assert_eq!(
+        Shell::Zsh.auto_env_script(None, Some("npm_config_registry,BUN_CONFIG_REGISTRY")),
+        "unset npm_config_registry\nunset BUN_CONFIG_REGISTRY\nunset RUST_NRM_ENV\n"
+    );
+    assert_eq!(
+        Shell::Fish.auto_env_script(None, Some("PATH,npm_config_registry")),
+        "set -e npm_config_registry\nset -e RUST_NRM_ENV\n"
+    );
+
+    // A variable the user exported is left alone
+    assert_eq!(Shell::Bash.auto_env_script(None, None), "");
+    assert_eq!(Shell::Powershell.auto_env_script(None, Some("")), "");
+}
+
+#[test]
+fn test_shell_init() {
+    for shell in [Shell::Bash, Shell::Zsh, Shell::Fish, Shell::Powershell] {
+        let script = shell.init_script();
+        assert!(script.contains(&format!("rust-nrm env --auto --shell {}", shell)));
+    }
+
+    let cli = Cli::try_parse_from(["rust-nrm", "shell-init", "fish"]).unwrap();
+    assert!(cli.command.writes_to_stdout());
+    assert!(matches!(
+        cli.command,
+        Commands::ShellInit { shell: Shell::Fish }
+    ));
+    assert!(Cli::try_parse_from(["rust-nrm", "env"]).is_err());
+    assert!(Cli::try_parse_from(["rust-nrm", "env", "--auto", "--shell", "zsh"]).is_ok());
+}
+
+#[test]
+fn test_completions() {
+    let dir = TempDir::new().unwrap();
+    let config = dir.path().join("registries.toml");
+    std::fs::write(
+        &config,
+        "[npm]\nregistry = \"https://registry.npmjs.org/\"\n\n\
+[company]\nregistry = \"https://npm.company.com/\"\n",
+    )
+    .unwrap();
+    std::env::set_var("RUST_NRM_CONFIG", &config);
+
+    // Registry names come from the config file at completion time
+    let candidates = |args: &[&str]| {
+        let args: Vec<OsString> = args.iter().map(OsString::from).collect();
+        let index = args.len() - 1;
+        complete(&mut Cli::command(), args, index, None)
+            .unwrap()
+            .into_iter()
+            .map(|candidate| candidate.get_value().to_string_lossy().into_owned())
+            .collect::<Vec<_>>()
+    };
+    assert!(candidates(&["rust-nrm", "use", "c"]).contains(&"company".to_string()));
+    assert!(candidates(&["rust-nrm", "remove", ""]).contains(&"npm".to_string()));
+    std::env::remove_var("RUST_NRM_CONFIG");
+
+    for shell in [
+        CompletionShell::Bash,
+        CompletionShell::Elvish,
+        CompletionShell::Fish,
+        CompletionShell::PowerShell,
+        CompletionShell::Zsh,
+    ] {
+        assert!(completion_script(shell).unwrap().contains("rust-nrm"));
+    }
+}
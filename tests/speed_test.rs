@@ -1,4 +1,53 @@
-use rust_nrm::utils::speed_test::{SpeedTestResult, SpeedTester};
+use rust_nrm::utils::speed_test::{
+    rank_results, FailureKind, LatencyStats, LiveTable, PackageSpec, SpeedTestResult, SpeedTester,
+    SpeedTesterConfig, TestFailure,
+};
+use rust_nrm::utils::timing::measure_phases;
+use serde_json::json;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+/// Serve `(path, status, body)` routes on a local port, 404 for anything
+/// else. `routes` gets the base URL so bodies can link back to the server.
+async fn serve(routes: impl FnOnce(&str) -> Vec<(&'static str, u16, String)>) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base = format!("http://{}/", listener.local_addr().unwrap());
+    let routes = routes(&base);
+    tokio::spawn(async move {
+        loop {
+            let Ok((mut socket, _)) = listener.accept().await else {
+                return;
+            };
+            let mut request = Vec::new();
+            let mut buf = [0; 1024];
+            // A TLS ClientHello never ends like an HTTP request, answer it right away
+            while !request.ends_with(b"\r\n\r\n") && request.first() != Some(&0x16) {
+                match socket.read(&mut buf).await {
+                    Ok(0) | Err(_) => break,
+                    Ok(n) => request.extend_from_slice(&buf[..n]),
+                }
+            }
+            let request = String::from_utf8_lossy(&request);
+            let path = request.split_whitespace().nth(1).unwrap_or("/");
+            let (status, body) = routes
+                .iter()
+                .find(|(route, _, _)| *route == path)
+                .map(|(_, status, body)| (*status, body.clone()))
+                .unwrap_or((404, String::new()));
+            let response = format!(
+                "HTTP/1.1 {} X\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                status,
+                body.len(),
+                body
+            );
+            let _ = socket.write_all(response.as_bytes()).await;
+        }
+    });
+    base
+}
 
 #[tokio::test]
 async fn test_speed_test_result() {
@@ -7,6 +56,7 @@ async fn test_speed_test_result() {
         url: "https://registry.npmjs.org/".to_string(),
         response_time: 0.1,
         is_success: true,
+        ..Default::default()
     };
 
     assert_eq!(result.name, "npm");
@@ -22,7 +72,7 @@ async fn test_speed_tester() {
         ("taobao".to_string(), "https://registry.npmmirror.com/".to_string()),
     ];
 
-    let tester = SpeedTester::new();
+    let tester = SpeedTester::new().unwrap();
     let results = tester.test_all(&registries).await;
 
     assert_eq!(results.len(), 2);
@@ -38,9 +88,472 @@ async fn test_speed_test_timeout() {
         ("invalid".to_string(), "https://invalid.registry.test".to_string()),
     ];
 
-    let tester = SpeedTester::new();
+    let tester = SpeedTester::new().unwrap();
     let results = tester.test_all(&registries).await;
 
     assert_eq!(results.len(), 1);
     assert!(!results[0].is_success); // Should fail for invalid registry
 }
+
+#[test]
+fn test_latency_stats() {
+    let stats = LatencyStats::from_samples(&[0.3, 0.1, 0.2, 0.4, 1.0]);
+    assert_eq!(stats.min, 0.1);
+    assert_eq!(stats.median, 0.3);
+    assert_eq!(stats.max, 1.0);
+    assert!((stats.p95 - 0.88).abs() < 1e-9);
+    assert!((stats.stddev - 0.3162).abs() < 1e-4);
+
+    assert_eq!(LatencyStats::from_samples(&[]), LatencyStats::default());
+    assert_eq!(LatencyStats::from_samples(&[0.2]).p95, 0.2);
+}
+
+#[test]
+fn test_rank_results() {
+    let result = |name: &str, response_time: f64, is_success: bool| SpeedTestResult {
+        name: name.to_string(),
+        response_time,
+        is_success,
+        ..Default::default()
+    };
+    let mut results = vec![
+        result("down", 0.01, false),
+        result("slow", 0.9, true),
+        result("broken", 0.02, false),
+        result("fast", 0.1, true),
+    ];
+    rank_results(&mut results);
+
+    let names: Vec<&str> = results.iter().map(|r| r.name.as_str()).collect();
+    assert_eq!(names, ["fast", "slow", "broken", "down"]);
+}
+
+#[tokio::test]
+async fn test_flaky_registry_ranks_last() {
+    // Answers immediately, but only every fifth request succeeds
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let flaky = format!("http://{}/", listener.local_addr().unwrap());
+    let requests = Arc::new(AtomicUsize::new(0));
+    tokio::spawn(async move {
+        while let Ok((mut socket, _)) = listener.accept().await {
+            let mut buf = [0; 1024];
+            let _ = socket.read(&mut buf).await;
+            let status = if requests.fetch_add(1, Ordering::SeqCst) % 5 == 4 {
+                "200 OK"
+            } else {
+                "503 Service Unavailable"
+            };
+            let response = format!(
+                "HTTP/1.1 {}\r\ncontent-length: 2\r\nconnection: close\r\n\r\n{{}}",
+                status
+            );
+            let _ = socket.write_all(response.as_bytes()).await;
+        }
+    });
+
+    // Always answers, after a delay
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let reliable = format!("http://{}/", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        while let Ok((mut socket, _)) = listener.accept().await {
+            let mut buf = [0; 1024];
+            let _ = socket.read(&mut buf).await;
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            let _ = socket
+                .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 2\r\nconnection: close\r\n\r\n{}")
+                .await;
+        }
+    });
+
+    let tester = SpeedTester::new().unwrap().with_warmup(0).with_samples(5);
+    let results = tester
+        .test_all(&[
+            ("flaky".to_string(), flaky),
+            ("reliable".to_string(), reliable),
+        ])
+        .await;
+
+    assert_eq!(results[0].name, "reliable");
+    assert_eq!(results[0].success_ratio, 1.0);
+    assert_eq!(results[1].name, "flaky");
+    assert!(results[1].is_success);
+    assert_eq!(results[1].success_ratio, 0.2);
+    assert!(results[1].response_time < results[0].response_time);
+}
+
+#[test]
+fn test_package_spec() {
+    let spec = PackageSpec::parse("lodash").unwrap();
+    assert_eq!(spec.version, None);
+    assert_eq!(
+        spec.packument_url("https://registry.npmjs.org/"),
+        "https://registry.npmjs.org/lodash"
+    );
+
+    let spec = PackageSpec::parse("@types/node@20").unwrap();
+    assert_eq!(spec.name, "@types/node");
+    assert_eq!(spec.version.as_deref(), Some("20"));
+    assert_eq!(spec.to_string(), "@types/node@20");
+    assert_eq!(
+        spec.packument_url("https://registry.npmmirror.com"),
+        "https://registry.npmmirror.com/@types%2fnode"
+    );
+
+    assert!(PackageSpec::parse("lodash@").is_err());
+    assert!(PackageSpec::parse("@types").is_err());
+    assert!(PackageSpec::parse("").is_err());
+
+    let packument = json!({
+        "dist-tags": { "latest": "2.0.0", "next": "3.0.0-rc.1" },
+        "versions": {
+            "1.0.0": { "dist": { "tarball": "https://r/pkg-1.0.0.tgz" } },
+            "2.0.0": { "dist": { "tarball": "https://r/pkg-2.0.0.tgz" } }
+        }
+    });
+    let tarball = |spec: &str| PackageSpec::parse(spec).unwrap().tarball_url(&packument);
+    assert_eq!(tarball("pkg").as_deref(), Some("https://r/pkg-2.0.0.tgz"));
+    assert_eq!(tarball("pkg@1.0.0").as_deref(), Some("https://r/pkg-1.0.0.tgz"));
+    assert_eq!(tarball("pkg@next"), None);
+    assert_eq!(tarball("pkg@9.9.9"), None);
+}
+
+#[tokio::test]
+async fn test_package_download() {
+    let registry = serve(|base| {
+        let packument = json!({
+            "dist-tags": { "latest": "1.0.0" },
+            "versions": {
+                "1.0.0": { "dist": { "tarball": format!("{}pkg/-/pkg-1.0.0.tgz", base) } }
+            }
+        });
+        vec![
+            ("/pkg", 200, packument.to_string()),
+            ("/pkg/-/pkg-1.0.0.tgz", 200, "x".repeat(4096)),
+        ]
+    })
+    .await;
+
+    let tester = SpeedTester::new()
+        .unwrap()
+        .with_samples(2)
+        .with_package(PackageSpec::parse("pkg").unwrap());
+    let result = tester.test_registry("local", &registry).await;
+    assert!(result.is_success);
+    assert_eq!(result.success_ratio, 1.0);
+    let download = result.download.unwrap();
+    assert_eq!(download.tarball_bytes, 4096);
+    assert!(download.ttfb <= download.total);
+    assert!(download.throughput > 0.0);
+
+    // A missing package is a failure, not a fast registry
+    let tester = SpeedTester::new()
+        .unwrap()
+        .with_package(PackageSpec::parse("missing").unwrap());
+    let result = tester.test_registry("local", &registry).await;
+    assert!(!result.is_success);
+    assert_eq!(result.download, None);
+}
+
+#[tokio::test]
+async fn test_failure_reasons() {
+    let base = serve(|_| {
+        vec![
+            ("/", 200, "{}".to_string()),
+            ("/private/", 401, "{}".to_string()),
+        ]
+    })
+    .await;
+    let tester = SpeedTester::new().unwrap().with_samples(2);
+
+    let result = tester.test_registry("ok", &base).await;
+    assert!(result.is_success);
+    assert_eq!(result.status, Some(200));
+    assert_eq!(result.failure, None);
+
+    // An error status is a response, but not a working registry
+    let result = tester
+        .test_registry("private", &format!("{}private/", base))
+        .await;
+    assert!(!result.is_success);
+    assert_eq!(result.status, Some(401));
+    let failure = result.failure.unwrap();
+    assert_eq!(failure.kind, FailureKind::Http);
+    assert_eq!(failure.message, "HTTP 401 Unauthorized");
+
+    let result = tester
+        .test_registry("tls", &base.replace("http://", "https://"))
+        .await;
+    assert_eq!(result.failure.unwrap().kind, FailureKind::Tls);
+
+    let result = tester.test_registry("refused", "http://127.0.0.1:1/").await;
+    assert_eq!(result.failure.unwrap().kind, FailureKind::Connect);
+
+    let mut results = tester
+        .test_all(&[
+            ("private".to_string(), format!("{}private/", base)),
+            ("ok".to_string(), base.clone()),
+        ])
+        .await;
+    rank_results(&mut results);
+    assert_eq!(results[0].name, "ok");
+    assert!(!results[1].is_success);
+}
+
+#[tokio::test]
+async fn test_detailed_phases() {
+    let base = serve(|_| vec![("/", 200, "{}".repeat(1000))]).await;
+    let tester = SpeedTester::new()
+        .unwrap()
+        .with_samples(3)
+        .with_detailed(true);
+
+    let result = tester.test_registry("local", &base).await;
+    assert!(result.is_success);
+    assert_eq!(result.status, Some(200));
+    let phases = result.phases.unwrap();
+    assert_eq!(phases.tls, None);
+    assert!(phases.dns + phases.connect + phases.ttfb + phases.body <= phases.total);
+
+    let (status, timings) = measure_phases(&base, Duration::from_secs(5)).await.unwrap();
+    assert_eq!(status, 200);
+    assert!(timings.total > 0.0);
+
+    let failure = measure_phases(&format!("{}missing", base), Duration::from_secs(5))
+        .await
+        .unwrap_err();
+    assert_eq!(failure.status, Some(404));
+    assert_eq!(failure.message, "HTTP 404 Not Found");
+
+    let failure = measure_phases(&base.replace("http://", "https://"), Duration::from_secs(5))
+        .await
+        .unwrap_err();
+    assert_eq!(failure.kind, FailureKind::Tls);
+
+    let failure = measure_phases("http://127.0.0.1:1/", Duration::from_secs(5))
+        .await
+        .unwrap_err();
+    assert_eq!(failure.kind, FailureKind::Connect);
+}
+
+/// Answer each request path with raw response parts, pausing between parts.
+/// The connection stays open afterwards, so a response only ends by its
+/// framing.
+async fn serve_raw(routes: Vec<(&'static str, Vec<String>)>) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        while let Ok((mut socket, _)) = listener.accept().await {
+            let mut buf = [0; 1024];
+            let read = socket.read(&mut buf).await.unwrap_or_default();
+            let request = String::from_utf8_lossy(&buf[..read]).to_string();
+            let path = request.split_whitespace().nth(1).unwrap_or("/").to_string();
+            let parts = routes
+                .iter()
+                .find(|(route, _)| *route == path)
+                .map(|(_, parts)| parts.clone())
+                .unwrap_or_default();
+            tokio::spawn(async move {
+                for (index, part) in parts.iter().enumerate() {
+                    if index > 0 {
+                        tokio::time::sleep(Duration::from_millis(200)).await;
+                    }
+                    let _ = socket.write_all(part.as_bytes()).await;
+                }
+                tokio::time::sleep(Duration::from_secs(30)).await;
+            });
+        }
+    });
+    base
+}
+
+#[tokio::test]
+async fn test_detailed_redirects_and_chunks() {
+    let redirect = |location: &str| {
+        vec![format!(
+            "HTTP/1.1 301 Moved Permanently\r\nlocation: {}\r\ncontent-length: 0\r\n\r\n",
+            location
+        )]
+    };
+    let base = serve_raw(vec![
+        ("/old", redirect("/new")),
+        // The first chunk's data ends like the last chunk
+        (
+            "/new",
+            vec![
+                "HTTP/1.1 200 OK\r\ntransfer-encoding: chunked\r\n\r\n7\r\nab0\r\n\r\n".to_string(),
+                "\r\n2;ext=1\r\n{}\r\n0\r\nx-trailer: 1\r\n\r\n".to_string(),
+            ],
+        ),
+        ("/loop", redirect("/loop")),
+    ])
+    .await;
+
+    let (status, timings) = measure_phases(&format!("{}/old", base), Duration::from_secs(5))
+        .await
+        .unwrap();
+    assert_eq!(status, 200);
+    // The body is only complete after the second part
+    assert!(timings.body >= 0.15, "{:?}", timings);
+
+    let failure = measure_phases(&format!("{}/loop", base), Duration::from_secs(5))
+        .await
+        .unwrap_err();
+    assert_eq!(failure.kind, FailureKind::Http);
+    assert_eq!(failure.status, Some(301));
+    assert_eq!(failure.message, "HTTP: more than 10 redirects");
+}
+
+#[tokio::test]
+async fn test_detailed_split_responses() {
+    let body = "x".repeat(100_000);
+    let base = serve_raw(vec![
+        // The end of the headers and a chunk size line straddle two parts
+        (
+            "/chunked",
+            vec![
+                "HTTP/1.1 200 OK\r\ntransfer-encoding: chunked\r\n\r".to_string(),
+                "\n1".to_string(),
+                "0\r\n0123456789abcdef\r\n0\r\n\r\n".to_string(),
+            ],
+        ),
+        (
+            "/length",
+            vec![
+                format!("HTTP/1.1 200 OK\r\ncontent-length: {}\r\n\r\n", body.len()),
+                body[..50_000].to_string(),
+                body[50_000..].to_string(),
+            ],
+        ),
+    ])
+    .await;
+
+    for path in ["/chunked", "/length"] {
+        let (status, timings) =
+            measure_phases(&format!("{}{}", base, path), Duration::from_secs(5))
+                .await
+                .unwrap();
+        assert_eq!(status, 200, "{}", path);
+        assert!(timings.body >= 0.3, "{}: {:?}", path, timings);
+    }
+}
+
+#[tokio::test]
+async fn test_retries_and_timeout() {
+    // Answers 503 to the first two requests, then 200
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let flaky = format!("http://{}/", listener.local_addr().unwrap());
+    let requests = Arc::new(AtomicUsize::new(0));
+    let counter = Arc::clone(&requests);
+    tokio::spawn(async move {
+        while let Ok((mut socket, _)) = listener.accept().await {
+            let mut buf = [0; 1024];
+            let _ = socket.read(&mut buf).await;
+            let status = if counter.fetch_add(1, Ordering::SeqCst) < 2 {
+                503
+            } else {
+                200
+            };
+            let response = format!(
+                "HTTP/1.1 {} X\r\ncontent-length: 2\r\nconnection: close\r\n\r\n{{}}",
+                status
+            );
+            let _ = socket.write_all(response.as_bytes()).await;
+        }
+    });
+
+    let config = SpeedTesterConfig {
+        retries: 2,
+        backoff: Duration::from_millis(10),
+        ..Default::default()
+    };
+    let result = SpeedTester::with_config(config.clone())
+        .unwrap()
+        .test_registry("flaky", &flaky)
+        .await;
+    assert!(result.is_success);
+    assert_eq!(requests.load(Ordering::SeqCst), 3);
+
+    // Client errors are final
+    let base = serve(|_| vec![("/", 404, String::new())]).await;
+    let result = SpeedTester::with_config(config)
+        .unwrap()
+        .test_registry("missing", &base)
+        .await;
+    assert_eq!(result.failure.unwrap().status, Some(404));
+
+    // Accepts connections but never answers
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let silent = format!("http://{}/", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        let mut sockets = Vec::new();
+        while let Ok((socket, _)) = listener.accept().await {
+            sockets.push(socket);
+        }
+    });
+    let config = SpeedTesterConfig {
+        timeout: Duration::from_millis(100),
+        concurrency: 1,
+        ..Default::default()
+    };
+    let tester = SpeedTester::with_config(config).unwrap();
+    let failure = tester
+        .test_registry("silent", &silent)
+        .await
+        .failure
+        .unwrap();
+    assert_eq!(failure.kind, FailureKind::Timeout);
+    assert_eq!(failure.message, "timeout: no response within 100ms");
+
+    let tester = tester.with_detailed(true);
+    let failure = tester
+        .test_registry("silent", &silent)
+        .await
+        .failure
+        .unwrap();
+    assert_eq!(failure.kind, FailureKind::Timeout);
+    assert_eq!(failure.message, "timeout: no response within 100ms");
+}
+
+#[test]
+fn test_live_table() {
+    colored::control::set_override(false);
+    let mut table = LiveTable::new(["npm", "taobao", "yarn"].map(String::from));
+    let rows = table.rows();
+    assert_eq!(rows.len(), 3);
+    assert!(rows.iter().all(|row| row.ends_with("testing...")));
+
+    table.finish(SpeedTestResult {
+        name: "yarn".to_string(),
+        failure: Some(TestFailure {
+            kind: FailureKind::Timeout,
+            status: None,
+            message: "timeout: no response within 5000ms".to_string(),
+        }),
+        ..Default::default()
+    });
+    table.finish(SpeedTestResult {
+        name: "taobao".to_string(),
+        response_time: 0.3,
+        is_success: true,
+        ..Default::default()
+    });
+    table.tick();
+    assert_eq!(
+        table.rows(),
+        [
+            "  #1  taobao  300ms",
+            "   ✗  yarn    timeout: no response within 5000ms",
+            "   ⠙  npm     testing...",
+        ]
+    );
+
+    // A faster result moves ahead of the ones already shown
+    table.finish(SpeedTestResult {
+        name: "npm".to_string(),
+        response_time: 0.1,
+        is_success: true,
+        ..Default::default()
+    });
+    assert_eq!(table.rows()[0], "  #1  npm     100ms");
+    assert_eq!(table.rows()[1], "  #2  taobao  300ms");
+}
@@ -0,0 +1,120 @@
+use rust_nrm::utils::auth::{Credentials, RegistryAuth};
+use rust_nrm::utils::package_manager::PackageManager;
+use rust_nrm::utils::targets::{
+    bunfig_path, load_target, load_target_at, local_target_path, pnpm_rc_path, save_target,
+};
+use tempfile::TempDir;
+
+const BUNFIG: &str = "# bun settings\n\
+[install]\n\
+exact = true\n\
+\n\
+[run]\n\
+shell = \"system\"\n";
+
+fn token(value: &str) -> RegistryAuth {
+    RegistryAuth {
+        credentials: Some(Credentials::Token(value.to_string())),
+        always_auth: false,
+    }
+}
+
+// Global pnpm and bun configs live under XDG_CONFIG_HOME, so everything that
+// depends on it is covered in one test to avoid races
+#[tokio::test]
+async fn test_global_targets() {
+    let dir = TempDir::new().unwrap();
+    std::env::set_var("XDG_CONFIG_HOME", dir.path());
+    assert_eq!(pnpm_rc_path().unwrap(), dir.path().join("pnpm").join("rc"));
+    assert_eq!(bunfig_path(false).unwrap(), dir.path().join(".bunfig.toml"));
+
+    // pnpm's rc is an ini file like .npmrc, created with its directory
+    let mut pnpm = load_target(PackageManager::Pnpm, false).await.unwrap();
+    pnpm.set_registry("https://registry.npmmirror.com/");
+    pnpm.set_scope("@company", "https://npm.company.com/");
+    save_target(pnpm.as_ref()).await.unwrap();
+    assert_eq!(
+        std::fs::read_to_string(dir.path().join("pnpm").join("rc")).unwrap(),
+        "registry=https://registry.npmmirror.com/\n@company:registry=https://npm.company.com/\n"
+    );
+
+    // bunfig.toml keeps unrelated settings and formatting
+    std::fs::write(dir.path().join(".bunfig.toml"), BUNFIG).unwrap();
+    let mut bun = load_target(PackageManager::Bun, false).await.unwrap();
+    assert_eq!(bun.package_manager(), PackageManager::Bun);
+    bun.set_registry("https://registry.npmmirror.com/");
+    bun.set_scope("@company", "https://npm.company.com/");
+    save_target(bun.as_ref()).await.unwrap();
+
+    let content = std::fs::read_to_string(dir.path().join(".bunfig.toml")).unwrap();
+    assert_eq!(
+        content,
+        "# bun settings\n\
+[install]\n\
+exact = true\n\
+registry = \"https://registry.npmmirror.com/\"\n\
+\n\
+[install.scopes]\n\
+company = \"https://npm.company.com/\"\n\
+\n\
+[run]\n\
+shell = \"system\"\n"
+    );
+    std::env::remove_var("XDG_CONFIG_HOME");
+}
+
+#[tokio::test]
+async fn test_bun_auth_and_scopes() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("bunfig.toml");
+    std::fs::write(
+        &path,
+        "[install.scopes]\ncompany = \"https://npm.company.com/\"\nother = \"https://other.com/\"\n",
+    )
+    .unwrap();
+
+    let mut bun = load_target_at(PackageManager::Bun, path.clone()).await.unwrap();
+
+    let key = bun.set_auth("https://npm.company.com", &token("secret")).unwrap();
+    assert_eq!(key, "install.scopes.company");
+    assert!(bun.set_auth("https://unknown.com/", &token("secret")).is_err());
+    assert!(bun
+        .render()
+        .contains("company = { url = \"https://npm.company.com/\", token = \"secret\" }"));
+
+    // Re-pointing the scope keeps its credentials
+    bun.set_scope("@company", "https://npm2.company.com/");
+    assert!(bun.render().contains("url = \"https://npm2.company.com/\""));
+    assert!(bun.render().contains("token = \"secret\""));
+
+    assert_eq!(bun.remove_auth("https://npm2.company.com/"), 1);
+    assert!(bun
+        .render()
+        .contains("company = \"https://npm2.company.com/\""));
+
+    assert!(bun.remove_scope("@company"));
+    assert!(bun.remove_scope("other"));
+    assert!(!bun.remove_scope("@company"));
+    assert_eq!(bun.render(), "");
+}
+
+#[test]
+fn test_local_target_path() {
+    let root = std::path::Path::new("/work/project");
+    assert_eq!(
+        local_target_path(PackageManager::Pnpm, root).unwrap(),
+        root.join(".npmrc")
+    );
+    assert_eq!(
+        local_target_path(PackageManager::YarnBerry, root).unwrap(),
+        root.join(".yarnrc.yml")
+    );
+    assert!(local_target_path(PackageManager::All, root).is_err());
+}
+
+#[tokio::test]
+async fn test_yarn_classic_has_no_auth() {
+    let mut yarn = load_target(PackageManager::Yarn, true).await.unwrap();
+    assert!(yarn.set_auth("https://registry.npmjs.org/", &token("x")).is_err());
+    assert!(load_target(PackageManager::All, true).await.is_err());
+}
@@ -0,0 +1,188 @@
+use rust_nrm::utils::auth::{remove_yarn_auth, set_yarn_auth, Credentials, RegistryAuth};
+use rust_nrm::utils::package_manager::PackageManager;
+use rust_nrm::utils::yarnrc::{yarn_registry_key, Yarnrc, YarnrcYml};
+use rust_nrm::utils::Error;
+use tempfile::TempDir;
+
+const SAMPLE: &str = concat!(
+    "# Yarn settings\n",
+    "nodeLinker: node-modules\n",
+    "\n",
+    "npmRegistryServer: \"https://registry.yarnpkg.com\" # default\n",
+    "plugins:\n",
+    "- path: .yarn/plugins/@yarnpkg/plugin-workspace-tools.cjs\n",
+    "  spec: \"@yarnpkg/plugin-workspace-tools\"\n",
+    "npmScopes:\n",
+    "    company:\n",
+    "        npmRegistryServer: \"https://npm.company.com\"\n",
+    "yarnPath: .yarn/releases/yarn-4.1.0.cjs\n",
+);
+
+#[test]
+fn test_parse_roundtrip_is_lossless() {
+    assert_eq!(YarnrcYml::parse(SAMPLE).to_string(), SAMPLE);
+
+    let crlf = "nodeLinker: pnp\r\n# note\r\n";
+    assert_eq!(YarnrcYml::parse(crlf).to_string(), crlf);
+}
+
+#[test]
+fn test_get_values() {
+    let yarnrc = YarnrcYml::parse(SAMPLE);
+    assert_eq!(
+        yarnrc.get(&["npmRegistryServer"]),
+        Some("https://registry.yarnpkg.com".to_string())
+    );
+    assert_eq!(
+        yarnrc.get(&["npmScopes", "company", "npmRegistryServer"]),
+        Some("https://npm.company.com".to_string())
+    );
+    assert_eq!(yarnrc.get(&["nodeLinker"]), Some("node-modules".to_string()));
+    assert_eq!(yarnrc.get(&["npmScopes"]), None);
+    assert_eq!(yarnrc.get(&["npmScopes", "other"]), None);
+}
+
+#[test]
+fn test_set_preserves_other_lines() {
+    let mut yarnrc = YarnrcYml::parse(SAMPLE);
+    yarnrc.set(&["npmRegistryServer"], "https://registry.npmmirror.com/");
+    yarnrc.set(
+        &["npmScopes", "acme", "npmRegistryServer"],
+        "https://npm.acme.dev/",
+    );
+
+    let expected = SAMPLE
+        .replace(
+            "\"https://registry.yarnpkg.com\" # default",
+            "\"https://registry.npmmirror.com/\" # default",
+        )
+        .replace(
+            "        npmRegistryServer: \"https://npm.company.com\"\n",
+            "        npmRegistryServer: \"https://npm.company.com\"\n    acme:\n        npmRegistryServer: \"https://npm.acme.dev/\"\n",
+        );
+    assert_eq!(yarnrc.to_string(), expected);
+}
+
+#[test]
+fn test_set_creates_missing_mappings() {
+    let mut yarnrc = YarnrcYml::parse("nodeLinker: pnp\n");
+    yarnrc.set(&["npmScopes", "company", "npmRegistryServer"], "https://a.com/");
+    assert_eq!(
+        yarnrc.to_string(),
+        "nodeLinker: pnp\nnpmScopes:\n  company:\n    npmRegistryServer: \"https://a.com/\"\n"
+    );
+
+    let mut empty = YarnrcYml::default();
+    empty.set(&["npmRegistryServer"], "https://a.com/");
+    assert_eq!(empty.to_string(), "npmRegistryServer: \"https://a.com/\"\n");
+}
+
+#[test]
+fn test_remove_cleans_up_empty_parents() {
+    let mut yarnrc = YarnrcYml::parse(SAMPLE);
+    assert!(yarnrc.remove(&["npmScopes", "company"]));
+    assert!(!yarnrc.remove(&["npmScopes", "company"]));
+
+    let content = yarnrc.to_string();
+    assert!(!content.contains("npmScopes"));
+    assert!(content.contains("yarnPath: .yarn/releases/yarn-4.1.0.cjs"));
+    assert!(content.contains("  spec: \"@yarnpkg/plugin-workspace-tools\""));
+}
+
+#[test]
+fn test_yarn_auth() {
+    let url = "https://npm.pkg.github.com/";
+    assert_eq!(yarn_registry_key(url), "//npm.pkg.github.com");
+
+    let mut yarnrc = YarnrcYml::default();
+    let auth = RegistryAuth {
+        credentials: Some(Credentials::Token("ghp_secret".to_string())),
+        always_auth: true,
+    };
+    set_yarn_auth(&mut yarnrc, url, &auth);
+    assert_eq!(
+        yarnrc.to_string(),
+        "npmRegistries:\n  \"//npm.pkg.github.com\":\n    npmAuthToken: \"ghp_secret\"\n    npmAlwaysAuth: true\n"
+    );
+
+    assert!(remove_yarn_auth(&mut yarnrc, url));
+    assert_eq!(yarnrc.to_string(), "");
+}
+
+#[tokio::test]
+async fn test_detect_package_manager() {
+    assert_eq!(
+        PackageManager::from_package_manager_field("yarn@4.1.0+sha512.abc"),
+        Some(PackageManager::YarnBerry)
+    );
+    assert_eq!(
+        PackageManager::from_package_manager_field("yarn@1.22.19"),
+        Some(PackageManager::Yarn)
+    );
+    assert_eq!(PackageManager::from_package_manager_field("pnpm"), None);
+
+    let dir = TempDir::new().unwrap();
+    assert_eq!(PackageManager::detect(dir.path()).await, PackageManager::Npm);
+
+    // A stray .yarnrc does not make the project a Yarn 1 project
+    std::fs::write(dir.path().join(".yarnrc"), "registry \"https://a.com/\"\n").unwrap();
+    assert_eq!(PackageManager::detect(dir.path()).await, PackageManager::Npm);
+
+    std::fs::write(dir.path().join(".yarnrc.yml"), "").unwrap();
+    assert_eq!(
+        PackageManager::detect(dir.path()).await,
+        PackageManager::YarnBerry
+    );
+
+    std::fs::write(
+        dir.path().join("package.json"),
+        r#"{ "packageManager": "npm@10.2.0" }"#,
+    )
+    .unwrap();
+    assert_eq!(PackageManager::detect(dir.path()).await, PackageManager::Npm);
+}
+
+#[test]
+fn test_yarnrc_classic() {
+    let content = concat!(
+        "# yarn lockfile v1\n",
+        "lastUpdateCheck 1700000000000\n",
+        "registry \"https://registry.yarnpkg.com\"\n",
+        "\"@company:registry\" \"https://npm.company.com/\"\n",
+    );
+    let mut yarnrc = Yarnrc::parse(content);
+    assert_eq!(yarnrc.to_string(), content);
+    assert_eq!(yarnrc.get("registry"), Some("https://registry.yarnpkg.com"));
+    assert_eq!(
+        yarnrc.get("@company:registry"),
+        Some("https://npm.company.com/")
+    );
+    assert_eq!(yarnrc.get("lastUpdateCheck"), Some("1700000000000"));
+
+    yarnrc.set("registry", "https://registry.npmmirror.com/");
+    yarnrc.set("@acme:registry", "https://npm.acme.dev/");
+    assert!(yarnrc.remove("@company:registry"));
+    assert_eq!(
+        yarnrc.to_string(),
+        concat!(
+            "# yarn lockfile v1\n",
+            "lastUpdateCheck 1700000000000\n",
+            "registry \"https://registry.npmmirror.com/\"\n",
+            "\"@acme:registry\" \"https://npm.acme.dev/\"\n",
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_yarnrc_io_errors() {
+    // A directory cannot be read as a file
+    let dir = TempDir::new().unwrap();
+    for err in [
+        Yarnrc::load(dir.path()).await.unwrap_err(),
+        YarnrcYml::load(dir.path()).await.unwrap_err(),
+    ] {
+        assert!(matches!(err, Error::PackageManagerConfig { .. }), "{:?}", err);
+        assert_eq!(err.exit_code(), 7);
+        assert!(err.to_string().starts_with("Failed to access package manager config"));
+    }
+}